@@ -7,28 +7,184 @@ use wagon::*;
 use game_consts::*;
 use astar::*;
 use pathfinding::*;
+use cargo::{ItemType, Task};
+use animation::*;
+use resource_manager::{TextureManager, TextureId};
+
+/// How much hunger and fatigue build up per second.
+const HUNGER_RATE: f32 = 1. / 240.;
+const FATIGUE_RATE: f32 = 1. / 360.;
+
+/// How much a galley or a bed restores per second.
+const EAT_RATE: f32 = 1. / 8.;
+const REST_RATE: f32 = 1. / 20.;
+
+/// Needs above this start to hurt movement and work.
+const NEEDS_THRESHOLD: f32 = 0.7;
+
+/// How big actors are drawn, and what they bump into things with.
+const BODY_SIZE: f32 = 32.;
+
+pub const ACTOR_HEALTH: f32 = 100.;
+
+/// Handed out to the crew in order as they join.
+const CREW_NAMES: [&'static str; 10] = ["ABEL", "BRIDGET", "CASPER", "DORA", "EZRA",
+                                        "FLORA", "GIDEON", "HETTY", "IVO", "JUNE"];
+
+/// The name actor number `id` goes by.
+pub fn crew_name(id: ActorId) -> &'static str {
+    CREW_NAMES[id as usize % CREW_NAMES.len()]
+}
+
+/// Needs meters, 0 is fully satisfied and 1 is starving / exhausted.
+pub struct Needs {
+    pub hunger: f32,
+    pub fatigue: f32,
+}
+
+impl Needs {
+    pub fn new() -> Self {
+        Needs {
+            hunger: 0.,
+            fatigue: 0.,
+        }
+    }
+
+    /// Drains the meters over time and restores them if standing on the right furniture.
+    pub fn update(&mut self, tile_type: Option<&TileType>, dt: f32) {
+        self.hunger += HUNGER_RATE * dt;
+        self.fatigue += FATIGUE_RATE * dt;
+
+        match tile_type {
+            Some(&TileType::Galley) => self.hunger -= EAT_RATE * dt,
+            Some(&TileType::Bed) => self.fatigue -= REST_RATE * dt,
+            _ => {}
+        }
+
+        self.hunger = self.hunger.max(0.).min(1.);
+        self.fatigue = self.fatigue.max(0.).min(1.);
+    }
+
+    /// Movement multiplier, goes down to 0.5 when exhausted or starving.
+    pub fn speed_factor(&self) -> f32 {
+        1. - 0.5 * Needs::penalty(self.hunger.max(self.fatigue))
+    }
+
+    /// Work output multiplier, goes down to 0.25 when exhausted or starving.
+    pub fn work_factor(&self) -> f32 {
+        1. - 0.75 * Needs::penalty(self.hunger.max(self.fatigue))
+    }
+
+    fn penalty(need: f32) -> f32 {
+        if need <= NEEDS_THRESHOLD {
+            0.
+        } else {
+            (need - NEEDS_THRESHOLD) / (1. - NEEDS_THRESHOLD)
+        }
+    }
+}
+
+/// Stays with an actor for its whole life, unlike its index in `ActorManager::actors`.
+pub type ActorId = u32;
+
+/// Standing orders actors keep following once their waypoints run out.
+#[derive(Clone, PartialEq)]
+pub enum Order {
+    /// Walk between the points in a loop, the index is the one we're heading to.
+    Patrol(Vec<Vector2f>, usize),
+    /// Stay close to another actor.
+    Follow(ActorId),
+    /// Stay at a spot and attack enemies that come near it.
+    Guard(Vector2f),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ActorKind {
+    Crew,
+    Engineer,
+}
+
+impl ActorKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ActorKind::Crew => "CREW",
+            ActorKind::Engineer => "ENGINEER",
+        }
+    }
+}
 
 pub struct Actor<'a> {
+    pub id: ActorId,
     pub sprite: Sprite<'a>,
+    /// Where the sprite was before the last simulation step, drawing blends from here.
+    pub prev_pos: Vector2f,
+    pub kind: ActorKind,
     pub inside_wagon: bool,
     pub move_seq: VecDeque<Vector2f>,
     pub rotation: f32,
+    pub needs: Needs,
+    pub carrying: Option<(ItemType, u32)>,
+    pub task: Option<Task>,
+    /// Progress towards finishing the current unit of work.
+    pub work: f32,
+    /// Points to walk to after the current path, in order.
+    pub waypoints: VecDeque<Vector2f>,
+    pub order: Option<Order>,
+    /// Seconds until the standing order may ask for a new path.
+    pub order_cooldown: f32,
+    /// Seconds until the next attack.
+    pub attack_cooldown: f32,
+    pub health: f32,
+    pub animation: Animation<'a>,
 }
 
 impl<'a> Actor<'a> {
-    pub fn new(texture: &'a Texture) -> Self {
+    pub fn new(tm: &'a TextureManager) -> Self {
         let mut sprite = Sprite::new().unwrap();
 //        sprite.set_size2f(25., 25.);
         sprite.set_position2f(1280. / 2. - 400., 215.);
         sprite.set_origin2f(16., 16.);
-        sprite.set_texture(texture, true);
+        sprite.set_texture(tm.get(TextureId::Char0Nm), true);
 
         Actor {
+            id: 0,
+            prev_pos: sprite.get_position(),
             sprite: sprite,
+            kind: ActorKind::Crew,
             inside_wagon: true,
             move_seq: VecDeque::new(),
             rotation: 0.,
+            needs: Needs::new(),
+            carrying: None,
+            task: None,
+            work: 0.,
+            waypoints: VecDeque::new(),
+            order: None,
+            order_cooldown: 0.,
+            attack_cooldown: 0.,
+            health: ACTOR_HEALTH,
+            animation: actor_animation(tm, BODY_SIZE),
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0.
+    }
+
+    /// Picks the clip for what the actor is doing and turns it towards where it's going.
+    pub fn animate(&mut self, dt: f32) {
+        if let Some(next) = self.move_seq.front().cloned() {
+            let pos = self.sprite.get_position();
+            // facing is kept relative to the wagon so it turns along with the train
+            let dir = formula_rot(&Vector2f::new(next.x - pos.x, next.y - pos.y), -self.rotation.to_radians());
+            self.animation.face(dir);
+            self.animation.play(AnimState::Walk);
+        } else {
+            self.animation.play(AnimState::Idle);
         }
+
+        self.animation.update(dt);
+        self.animation.apply(&mut self.sprite, self.rotation);
     }
 }
 
@@ -52,7 +208,8 @@ impl<'a> Pathfinding for Actor<'a> {
         self.move_seq.pop_front();
     }
     fn get_collision_bounds(&self) -> FloatRect {
-        self.sprite.get_global_bounds()
+        let pos = self.sprite.get_position();
+        FloatRect::new(pos.x - BODY_SIZE / 2., pos.y - BODY_SIZE / 2., BODY_SIZE, BODY_SIZE)
     }
     fn move2f(&mut self, x: f32, y: f32) {
         self.sprite.move2f(x, y);
@@ -60,4 +217,7 @@ impl<'a> Pathfinding for Actor<'a> {
     fn set_inside_wagon(&mut self, inside: bool) {
         self.inside_wagon = inside;
     }
+    fn get_speed_factor(&self) -> f32 {
+        self.needs.speed_factor()
+    }
 }
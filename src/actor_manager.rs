@@ -1,20 +1,79 @@
 use sfml::graphics::*;
 use sfml::system::*;
-use std::collections::VecDeque;
+use std::collections::{VecDeque, HashSet};
 use std::thread::JoinHandle;
 use std::thread;
-use actor::Actor;
+use actor::{Actor, ActorId, ActorKind, Order};
+use enemy::Enemy;
+use animation::AnimState;
+use wagon::formula_rot;
+use resource_manager::{FontManager, FontId};
 use std::sync::mpsc::*;
 use pathfinding::*;
 use resource_manager::{TextureManager, TextureId};
+use cargo::{self, ItemType, Task};
+use train::Train;
+use console::{CommandRegistry, CommandContext};
+use boiler::STOKE_RATE;
+use timestep::offset_states;
+use game_consts::*;
+
+/// Clicks closer together than this count as a double click.
+const DOUBLE_CLICK_TIME: f32 = 0.3;
+
+/// How far, in tiles, actors outside may stray from the click when moving as a group.
+const FORMATION_SPREAD: f32 = 3.;
+
+/// How close enemies have to get to a guard post before the guard goes after them.
+const GUARD_RANGE: f32 = 5. * TILE_SIZE_X as f32;
+
+/// Followers catch up once they're further away than this.
+const FOLLOW_DISTANCE: f32 = 1.5 * TILE_SIZE_X as f32;
+
+/// Standing orders only ask for a new path this often, in seconds.
+const ORDER_COOLDOWN: f32 = 0.5;
+
+const ATTACK_RANGE: f32 = 48.;
+const ATTACK_DAMAGE: f32 = 25.;
+const ATTACK_COOLDOWN: f32 = 0.8;
+
+/// Drag rectangles smaller than this are treated as a single click.
+const CLICK_SIZE: f32 = 4.;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SelectMode {
+    /// Throw away the old selection.
+    Replace,
+    /// Shift: add to the old selection.
+    Add,
+    /// Ctrl: flip whatever is under the cursor.
+    Toggle,
+}
 
 pub struct ActorManager<'a> {
     pub actors: Vec<Actor<'a>>,
     pub selected: Vec<usize>,
     selection_rect: RectangleShape<'a>,
     is_selecting: bool,
-    handles: Vec<(usize, JoinHandle<Option<VecDeque<(i32, i32)>>>)>,
-    channel: (Sender<usize>, Receiver<usize>),
+    /// Paths being computed, by actor and request number. An actor can have more than one
+    /// going when it's given new orders, only the newest one counts.
+    handles: Vec<(ActorId, u32, JoinHandle<Option<VecDeque<(i32, i32)>>>)>,
+    channel: (Sender<u32>, Receiver<u32>),
+    next_request: u32,
+    pub haul_item: ItemType,
+    groups: [Vec<usize>; 9],
+    /// Seconds of play, for telling double clicks apart. Kept here rather than read off a
+    /// wall clock so replays see the same clicks as double.
+    input_time: f32,
+    last_click: Option<(usize, f32)>,
+    last_recall: Option<(usize, f32)>,
+    /// Wait for paths to finish in the tick they were asked for, replays need that.
+    pub wait_for_paths: bool,
+    next_id: ActorId,
+    /// Drawn once for every waypoint and order marker.
+    marker: CircleShape<'a>,
+    marker_text: Text<'a>,
+    font: Option<&'a Font>,
 }
 
 impl<'a> ActorManager<'a> {
@@ -23,6 +82,15 @@ impl<'a> ActorManager<'a> {
         selection_rect.set_size2f(0., 0.);
         selection_rect.set_fill_color(&Color::new_rgba(0, 255, 0, 150));
 
+        let mut marker = CircleShape::new().unwrap();
+        marker.set_radius(14.);
+        marker.set_origin2f(14., 14.);
+        marker.set_fill_color(&Color::new_rgba(0, 0, 0, 120));
+        marker.set_outline_thickness(2.);
+
+        let mut marker_text = Text::new().unwrap();
+        marker_text.set_character_size(14);
+
         ActorManager {
             actors: vec![],
             selected: vec![],
@@ -30,42 +98,111 @@ impl<'a> ActorManager<'a> {
             is_selecting: false,
             handles: vec![],
             channel: channel(),
+            next_request: 0,
+            haul_item: ItemType::Coal,
+            groups: Default::default(),
+            input_time: 0.,
+            last_click: None,
+            last_recall: None,
+            wait_for_paths: false,
+            next_id: 0,
+            marker: marker,
+            marker_text: marker_text,
+            font: None,
         }
     }
 
-    pub fn init_actors(&mut self, tm: &'a TextureManager) {
-        self.actors = vec![Actor::new(&tm.get(TextureId::Char0Nm)),
-                           Actor::new(&tm.get(TextureId::Char0Nm)),
-                           Actor::new(&tm.get(TextureId::Char0Nm)),
-                           Actor::new(&tm.get(TextureId::Char0Nm)),
-                           Actor::new(&tm.get(TextureId::Char0Nm))];
+    pub fn spawn_actor(&mut self, tm: &'a TextureManager, kind: ActorKind, pos: Vector2f) {
+        let mut actor = Actor::new(tm);
+        actor.id = self.next_id;
+        self.next_id += 1;
+        actor.kind = kind;
+        actor.sprite.set_position(&pos);
+        actor.prev_pos = pos;
+        self.actors.push(actor);
+    }
 
-        // move them a bit
-        let mut offset = 256.;
-        for a in self.actors.iter_mut() {
-            a.sprite.move2f(offset, 96.);
-            offset += 64.;
+    pub fn init_font(&mut self, fm: &'a FontManager) {
+        let font = fm.get(FontId::Joystix);
+        self.marker_text.set_font(font);
+        self.font = Some(font);
+    }
+
+    /// Takes the dead out of `actors`, fixing up the selection and groups that point into it.
+    /// Returns who died.
+    pub fn remove_dead(&mut self) -> Vec<ActorId> {
+        let dead: Vec<ActorId> = self.actors.iter().filter(|a| a.is_dead()).map(|a| a.id).collect();
+        if dead.is_empty() {
+            return dead;
+        }
+
+        // old index -> new index, None for the dead
+        let mut new_index = vec![];
+        let mut n = 0;
+        for a in self.actors.iter() {
+            if a.is_dead() {
+                new_index.push(None);
+            } else {
+                new_index.push(Some(n));
+                n += 1;
+            }
+        }
+        let remap = |list: &Vec<usize>| -> Vec<usize> { list.iter().filter_map(|&idx| new_index[idx]).collect() };
+
+        self.selected = remap(&self.selected);
+        for group in self.groups.iter_mut() {
+            *group = remap(group);
         }
+        self.last_click = None;
+        self.actors.retain(|a| !a.is_dead());
+        dead
     }
 
-    pub fn update_threads(&mut self, train_origin: Vector2f) {
-        for recv in self.channel.1.try_iter() {
-            let true_index = self.handles.iter().position(|ref x| x.0 == recv).unwrap();
-            let path = self.handles.remove(true_index).1.join().unwrap();
+    /// Where actor `id` currently is in `actors`, if it's still around.
+    pub fn index_of(&self, id: ActorId) -> Option<usize> {
+        self.actors.iter().position(|a| a.id == id)
+    }
+
+    /// Picks up finished paths. `dt` is the frame time, paused or not.
+    pub fn update_threads(&mut self, train_origin: Vector2f, dt: f32) {
+        self.input_time += dt;
+
+        let done: Vec<u32> = if self.wait_for_paths {
+            self.handles.iter().map(|h| h.1).collect()
+        } else {
+            self.channel.1.try_iter().collect()
+        };
+        for request in done {
+            let true_index = self.handles.iter().position(|h| h.1 == request).unwrap();
+            let (id, _, handle) = self.handles.remove(true_index);
+            let path = handle.join().unwrap();
+
+            // a newer order asked for another path since, or the actor is gone
+            if self.handles.iter().any(|h| h.0 == id && h.1 > request) {
+                continue;
+            }
+            let idx = match self.index_of(id) {
+                Some(idx) => idx,
+                None => continue,
+            };
+
             if let Some(mut p) = path {
-                self.actors[self.selected[recv]].set_path(&mut p,
-                                                          train_origin);
+                self.actors[idx].set_path(&mut p, train_origin);
             } else {
                 println!("no path!");
             }
 
         }
+        if self.wait_for_paths {
+            // the threads have all been joined, so their messages are in and nobody needs them
+            self.channel.1.try_iter().count();
+        }
     }
 
-    pub fn update_selection_rect(&mut self, mouse_x: i32, mouse_y: i32) {
+    pub fn update_selection_rect(&mut self, coords: &Vector2f) {
         if self.is_selecting {
             let rect_pos = self.selection_rect.get_position();
-            self.selection_rect.set_size2f(-(rect_pos.x - mouse_x as f32), -(rect_pos.y - mouse_y as f32));
+            self.selection_rect.set_size2f(-(rect_pos.x - coords.x), -(rect_pos.y - coords.y));
         }
     }
 
@@ -75,74 +212,537 @@ impl<'a> ActorManager<'a> {
         self.selection_rect.set_position(coords);
     }
 
+    /// Sends every selected actor towards `click_pos`. With `spread` each one gets its own free tile,
+    /// nearest tiles first, and actors outside the train keep roughly their place in the group.
     pub fn launch_movement_orders(&mut self,
                                   pfgrid_in: PathfindingGrid,
                                   pfgrid_out: PathfindingGrid,
                                   train_pos: Vector2f,
-                                  click_pos: Vector2f) {
-        if !self.selected.is_empty() {
-            for (idx, sa) in self.selected.iter().enumerate() {
-                let actor = &mut self.actors[*sa];
+                                  click_pos: Vector2f,
+                                  spread: bool) {
+        let center = match self.selection_center() {
+            Some(center) => center,
+            None => return,
+        };
+
+        // closest actors claim the closest tiles
+        let mut order = self.selected.clone();
+        {
+            let actors = &self.actors;
+            let dist = |idx: usize| {
+                let pos = actors[idx].sprite.get_position();
+                (pos.x - click_pos.x).powi(2) + (pos.y - click_pos.y).powi(2)
+            };
+            order.sort_by(|&a, &b| dist(a).partial_cmp(&dist(b)).unwrap());
+        }
+
+        let mut taken = HashSet::new();
+        for idx in order {
+            let (inside, pos) = (self.actors[idx].inside_wagon, self.actors[idx].sprite.get_position());
+            let pfgrid_to_use = if inside { &pfgrid_in } else { &pfgrid_out };
+
+            let dest = if !spread {
+                click_pos
+            } else {
+                let desired = if inside {
+                    click_pos
+                } else {
+                    // keep the shape of the group on open ground
+                    let max = FORMATION_SPREAD * TILE_SIZE_X as f32;
+                    Vector2f::new(click_pos.x + (pos.x - center.x).max(-max).min(max),
+                                  click_pos.y + (pos.y - center.y).max(-max).min(max))
+                };
+
+                let from = world_to_grid(desired, pfgrid_to_use, train_pos);
+                match nearest_free_tile(pfgrid_to_use, from, &taken) {
+                    Some(tile) => {
+                        taken.insert(tile);
+                        grid_to_world(tile, pfgrid_to_use, train_pos)
+                    }
+                    None => continue,
+                }
+            };
+
+            self.request_path(idx, pfgrid_to_use.clone(), train_pos, dest);
+        }
+    }
+
+    /// Computes a path for actor `idx` on a separate thread, picked up in `update_threads`.
+    pub fn request_path(&mut self, idx: usize, grid: PathfindingGrid, train_pos: Vector2f, dest: Vector2f) {
+        let start = self.actors[idx].sprite.get_position();
+        let send = self.channel.0.clone();
+        let request = self.next_request;
+        self.next_request += 1;
+        self.handles.push((self.actors[idx].id, request, thread::spawn(move || {
+            let path = compute_path(start, grid, train_pos, dest);
+            send.send(request);
+            path
+        })));
+    }
+
+    /// Appends `pos` to the waypoints of every selected actor.
+    pub fn queue_waypoint(&mut self, pos: Vector2f) {
+        for sa in self.selected.iter() {
+            self.actors[*sa].waypoints.push_back(pos);
+        }
+    }
+
+    /// Drops the queued waypoints and standing orders of the selected actors.
+    pub fn clear_orders(&mut self) {
+        for sa in self.selected.iter() {
+            let a = &mut self.actors[*sa];
+            a.waypoints.clear();
+            a.order = None;
+        }
+    }
+
+    /// Gives every selected actor a standing order, replacing whatever they were doing.
+    pub fn give_order(&mut self, order: Order) {
+        for sa in self.selected.iter() {
+            // nobody follows themselves
+            if order == Order::Follow(self.actors[*sa].id) {
+                continue;
+            }
+
+            let a = &mut self.actors[*sa];
+            a.waypoints.clear();
+            a.clear_steps();
+            a.task = None;
+            a.order = Some(order.clone());
+            a.order_cooldown = 0.;
+        }
+    }
+
+    /// Adds `pos` to the patrol route of the selected actors, starting a new route from where they stand.
+    pub fn add_patrol_point(&mut self, pos: Vector2f) {
+        for sa in self.selected.iter() {
+            let a = &mut self.actors[*sa];
+            let extended = if let Some(Order::Patrol(ref mut points, _)) = a.order {
+                points.push(pos);
+                true
+            } else {
+                false
+            };
 
-                let pfgrid_to_use = if actor.inside_wagon {
+            if !extended {
+                a.waypoints.clear();
+                a.task = None;
+                a.order = Some(Order::Patrol(vec![a.sprite.get_position(), pos], 1));
+            }
+        }
+    }
+
+    /// Walks actors through their waypoints and standing orders, and lets them fight enemies in reach.
+    pub fn update_orders(&mut self,
+                         pfgrid_in: &PathfindingGrid,
+                         pfgrid_out: &PathfindingGrid,
+                         train_pos: Vector2f,
+                         enemies: &mut [Enemy],
+                         dt: f32) {
+        for idx in 0..self.actors.len() {
+            let pos = self.actors[idx].sprite.get_position();
+
+            // anyone fights back when an enemy is in reach
+            {
+                let a = &mut self.actors[idx];
+                a.order_cooldown -= dt;
+                a.attack_cooldown -= dt;
+
+                if a.attack_cooldown <= 0. {
+                    if let Some(e) = nearest_enemy(enemies, pos, ATTACK_RANGE) {
+                        enemies[e].health -= ATTACK_DAMAGE * a.needs.work_factor();
+                        a.attack_cooldown = ATTACK_COOLDOWN;
+
+                        let enemy_pos = enemies[e].sprite.get_position();
+                        let dir = formula_rot(&Vector2f::new(enemy_pos.x - pos.x, enemy_pos.y - pos.y),
+                                              -a.rotation.to_radians());
+                        a.animation.face(dir);
+                        a.animation.play(AnimState::Attack);
+                    }
+                }
+            }
+
+            // still walking or waiting for a path
+            let id = self.actors[idx].id;
+            if !self.actors[idx].steps_are_empty() || self.handles.iter().any(|h| h.0 == id) {
+                continue;
+            }
+
+            let dest = if let Some(wp) = self.actors[idx].waypoints.pop_front() {
+                Some(wp)
+            } else if self.actors[idx].order_cooldown > 0. {
+                None
+            } else {
+                match self.actors[idx].order.clone() {
+                    Some(Order::Patrol(points, next)) => {
+                        self.actors[idx].order = Some(Order::Patrol(points.clone(), (next + 1) % points.len()));
+                        Some(points[next])
+                    }
+                    Some(Order::Follow(target)) => {
+                        match self.index_of(target) {
+                            Some(target) => {
+                                let target_pos = self.actors[target].sprite.get_position();
+                                if distance(pos, target_pos) > FOLLOW_DISTANCE {
+                                    Some(target_pos)
+                                } else {
+                                    None
+                                }
+                            }
+                            // nobody left to follow
+                            None => {
+                                self.actors[idx].order = None;
+                                None
+                            }
+                        }
+                    }
+                    Some(Order::Guard(post)) => {
+                        match nearest_enemy(enemies, post, GUARD_RANGE) {
+                            Some(e) => {
+                                let enemy_pos = enemies[e].sprite.get_position();
+                                if distance(pos, enemy_pos) > ATTACK_RANGE {
+                                    Some(enemy_pos)
+                                } else {
+                                    None
+                                }
+                            }
+                            None if distance(pos, post) > TILE_SIZE_X as f32 / 2. => Some(post),
+                            None => None,
+                        }
+                    }
+                    None => None,
+                }
+            };
+
+            if let Some(dest) = dest {
+                let grid = if self.actors[idx].inside_wagon {
                     pfgrid_in.clone()
                 } else {
                     pfgrid_out.clone()
                 };
-
-                let start = actor.sprite.get_position();
-                let send = self.channel.0.clone();
-                self.handles.push((idx, thread::spawn(move || {
-                    let path = compute_path(start,
-                                            pfgrid_to_use,
-                                            train_pos,
-                                            click_pos);
-                    send.send(idx);
-                    path
-                })));
+                self.actors[idx].order_cooldown = ORDER_COOLDOWN;
+                self.request_path(idx, grid, train_pos, dest);
             }
         }
     }
 
-    pub fn apply_selection(&mut self) {
-        if self.is_selecting {
-            self.is_selecting = false;
-
-            self.selected.clear();
-            for (idx, a) in self.actors.iter_mut().enumerate() {
-                if a.sprite
-                    .get_global_bounds()
-                    .intersects(&self.selection_rect.get_global_bounds()) != None {
-                        if !self.selected.contains(&idx) {
-                            a.sprite.set_color(&Color::green());
-                            self.selected.push(idx);
+    /// Throws away actor `idx`'s path and plans a new one to the same place that avoids `tile`.
+    pub fn replan(&mut self,
+                  idx: usize,
+                  tile: (i32, i32),
+                  pfgrid_in: &PathfindingGrid,
+                  pfgrid_out: &PathfindingGrid,
+                  train_pos: Vector2f) {
+        let dest = match self.actors[idx].move_seq.back() {
+            Some(dest) => *dest,
+            None => return,
+        };
+
+        let mut grid = if self.actors[idx].inside_wagon {
+            pfgrid_in.clone()
+        } else {
+            pfgrid_out.clone()
+        };
+        let (x, y) = tile;
+        if x >= 0 && y >= 0 && (x as usize) < grid.grid.len() && (y as usize) < grid.grid[0].len() {
+            grid.grid[x as usize][y as usize].walkable = false;
+        }
+
+        self.actors[idx].clear_steps();
+        self.request_path(idx, grid, train_pos, dest);
+    }
+
+    /// Gives every selected actor `task` to carry out once it arrives.
+    pub fn set_task(&mut self, task: Option<Task>) {
+        for sa in self.selected.iter() {
+            self.actors[*sa].task = task;
+        }
+    }
+
+    /// Carries out the tasks of actors that have finished walking.
+    pub fn update_tasks(&mut self, train: &mut Train, dt: f32) {
+        for a in self.actors.iter_mut() {
+            let task = match a.task {
+                Some(task) => task,
+                None => continue,
+            };
+
+            // still walking or waiting for a path
+            if !a.steps_are_empty() || self.handles.iter().any(|h| h.0 == a.id) {
+                continue;
+            }
+
+            let here = train.tile_index_at(a.sprite.get_position());
+
+            match task {
+                Task::Haul((w, i, j)) => {
+                    if here == Some((w, i, j)) {
+                        if let Some(ref mut storage) = train.wagons[w].tiles[i][j].storage {
+                            cargo::haul(&mut a.carrying, storage, self.haul_item);
+                        }
+                    }
+                }
+                Task::Stoke(t) => {
+                    if here == Some(t) {
+                        if let Some((ItemType::Coal, amount)) = a.carrying {
+                            // tired or hungry stokers shovel slower
+                            a.work += STOKE_RATE * a.needs.work_factor() * dt;
+                            if a.work >= 1. {
+                                a.work -= 1.;
+                                // coal goes in by the lump, only shovel one in if all of it fits
+                                if train.boiler.free_space() >= 1. {
+                                    train.boiler.stoke(1.);
+                                    a.carrying = if amount > 1 { Some((ItemType::Coal, amount - 1)) } else { None };
+                                } else {
+                                    // firebox is full, stop here
+                                    a.work = 0.;
+                                    a.task = None;
+                                }
+                            }
+                            continue;
                         }
                     }
-                else if self.selected.get(idx) == None {
-                    a.sprite.set_color(&Color::white());
                 }
             }
+
+            // either done or it never got there
+            a.work = 0.;
+            a.task = None;
         }
     }
 
-    pub fn draw(&self, window: &mut RenderWindow) {
+    /// Total number of items currently carried by actors.
+    pub fn carried(&self) -> u32 {
+        self.actors.iter().filter_map(|a| a.carrying).map(|c| c.1).sum()
+    }
+
+    /// Selects actors inside the drag rectangle, or the one under the cursor for a plain click.
+    /// `visible` is the part of the world on screen, used by double click.
+    pub fn apply_selection(&mut self, mode: SelectMode, visible: &FloatRect) {
+        if !self.is_selecting {
+            return;
+        }
+        self.is_selecting = false;
+
+        let size = self.selection_rect.get_size();
+        let picked: Vec<usize> = if size.x.abs() < CLICK_SIZE && size.y.abs() < CLICK_SIZE {
+            let click_pos = self.selection_rect.get_position();
+            match self.actor_at(click_pos) {
+                Some(idx) => {
+                    if self.is_double_click(idx) {
+                        self.visible_of_kind(self.actors[idx].kind, visible)
+                    } else {
+                        vec![idx]
+                    }
+                }
+                None => vec![],
+            }
+        } else {
+            let rect = self.selection_rect.get_global_bounds();
+            (0..self.actors.len())
+                .filter(|&idx| self.actors[idx].get_collision_bounds().intersects(&rect) != None)
+                .collect()
+        };
+
+        match mode {
+            SelectMode::Replace => {
+                self.selected = picked;
+            }
+            SelectMode::Add => {
+                for idx in picked {
+                    if !self.selected.contains(&idx) {
+                        self.selected.push(idx);
+                    }
+                }
+            }
+            SelectMode::Toggle => {
+                for idx in picked {
+                    if let Some(pos) = self.selected.iter().position(|&s| s == idx) {
+                        self.selected.remove(pos);
+                    } else {
+                        self.selected.push(idx);
+                    }
+                }
+            }
+        }
+
+        self.refresh_colors();
+    }
+
+    /// Replaces the selection with `selected`.
+    pub fn select(&mut self, selected: Vec<usize>) {
+        self.selected = selected;
+        self.refresh_colors();
+    }
+
+    fn refresh_colors(&mut self) {
+        for (idx, a) in self.actors.iter_mut().enumerate() {
+            if self.selected.contains(&idx) {
+                a.sprite.set_color(&Color::green());
+            } else {
+                a.sprite.set_color(&Color::white());
+            }
+        }
+    }
+
+    /// Topmost actor under `pos`.
+    pub fn actor_at(&self, pos: Vector2f) -> Option<usize> {
+        self.actors.iter().rposition(|a| a.get_collision_bounds().contains(pos))
+    }
+
+    fn is_double_click(&mut self, idx: usize) -> bool {
+        let double = match self.last_click {
+            Some((last, at)) => last == idx && self.input_time - at < DOUBLE_CLICK_TIME,
+            None => false,
+        };
+        self.last_click = if double { None } else { Some((idx, self.input_time)) };
+        double
+    }
+
+    fn visible_of_kind(&self, kind: ActorKind, visible: &FloatRect) -> Vec<usize> {
+        (0..self.actors.len())
+            .filter(|&idx| self.actors[idx].kind == kind && visible.contains(self.actors[idx].sprite.get_position()))
+            .collect()
+    }
+
+    /// Stores the current selection as control group `n`.
+    pub fn assign_group(&mut self, n: usize) {
+        self.groups[n] = self.selected.clone();
+    }
+
+    /// Selects control group `n`. Recalling the same group twice quickly returns where to centre the camera.
+    pub fn recall_group(&mut self, n: usize) -> Option<Vector2f> {
+        if self.groups[n].is_empty() {
+            return None;
+        }
+
+        let group = self.groups[n].clone();
+        self.select(group);
+
+        let double = match self.last_recall {
+            Some((last, at)) => last == n && self.input_time - at < DOUBLE_CLICK_TIME * 2.,
+            None => false,
+        };
+        self.last_recall = Some((n, self.input_time));
+
+        if double {
+            self.selection_center()
+        } else {
+            None
+        }
+    }
+
+    /// Average position of the selected actors.
+    pub fn selection_center(&self) -> Option<Vector2f> {
+        if self.selected.is_empty() {
+            return None;
+        }
+
+        let mut sum = Vector2f::new(0., 0.);
+        for sa in self.selected.iter() {
+            sum = sum + self.actors[*sa].sprite.get_position();
+        }
+        Some(Vector2f::new(sum.x / self.selected.len() as f32, sum.y / self.selected.len() as f32))
+    }
+
+    /// `alpha` is how far along the current simulation step we are, actors are drawn that far
+    /// between where they were and where they are.
+    pub fn draw(&mut self, window: &mut RenderWindow, alpha: f32) {
+        // every path goes into one vertex array and out in a single draw call
+        let mut paths = VertexArray::new().unwrap();
+        paths.set_primitive_type(PrimitiveType::sfLines);
         for a in self.actors.iter() {
-            // draw path
-            let steps = Vec::from(a.move_seq.clone());
-            for step in steps.windows(2) {
-                let mut va = VertexArray::new().unwrap();
-                va.set_primitive_type(PrimitiveType::sfLines);
-                va.append(&Vertex::new_with_pos_color(&step[0], &Color::green()));
-                va.append(&Vertex::new_with_pos_color(&step[1], &Color::green()));
-                window.draw(&va);
+            let mut steps = a.move_seq.iter();
+            if let Some(mut from) = steps.next() {
+                for to in steps {
+                    paths.append(&Vertex::new_with_pos_color(from, &Color::green()));
+                    paths.append(&Vertex::new_with_pos_color(to, &Color::green()));
+                    from = to;
+                }
             }
+        }
+        window.draw(&paths);
 
-            // draw actor
-            window.draw(&a.sprite);
+        for a in self.actors.iter() {
+            let offset = (a.prev_pos - a.sprite.get_position()) * (1. - alpha);
+            window.draw_with_renderstates(&a.sprite, &mut offset_states(offset));
+        }
+
+        // queued waypoints and standing orders of the selected actors
+        let mut markers = vec![];
+        for sa in self.selected.iter() {
+            let a = &self.actors[*sa];
+            for (n, wp) in a.waypoints.iter().enumerate() {
+                markers.push((*wp, (n + 1).to_string(), Color::green()));
+            }
+            match a.order {
+                Some(Order::Patrol(ref points, _)) => {
+                    for (n, p) in points.iter().enumerate() {
+                        markers.push((*p, format!("P{}", n + 1), Color::cyan()));
+                    }
+                }
+                Some(Order::Guard(post)) => {
+                    markers.push((post, String::from("G"), Color::red()));
+                }
+                _ => {}
+            }
+        }
+        for (pos, label, color) in markers {
+            self.draw_marker(window, &pos, &label, &color);
         }
 
         if self.is_selecting {
             window.draw(&self.selection_rect);
         }
     }
+
+    fn draw_marker(&mut self, window: &mut RenderWindow, pos: &Vector2f, label: &str, color: &Color) {
+        self.marker.set_position(pos);
+        self.marker.set_outline_color(color);
+        window.draw(&self.marker);
+
+        if self.font.is_some() {
+            self.marker_text.set_color(color);
+            self.marker_text.set_string(label);
+            self.marker_text.set_position2f(pos.x - 7. * label.len() as f32 / 2. - 2., pos.y - 10.);
+            window.draw(&self.marker_text);
+        }
+    }
+}
+
+fn distance(a: Vector2f, b: Vector2f) -> f32 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+/// Index of the closest living enemy within `range` of `pos`.
+fn nearest_enemy(enemies: &[Enemy], pos: Vector2f, range: f32) -> Option<usize> {
+    let mut best = None;
+    let mut best_dist = range;
+    for (idx, e) in enemies.iter().enumerate() {
+        let d = distance(pos, e.sprite.get_position());
+        if e.health > 0. && d <= best_dist {
+            best = Some(idx);
+            best_dist = d;
+        }
+    }
+    best
+}
+
+pub fn register_commands(registry: &mut CommandRegistry) {
+    registry.register("tp", "teleport the selected actors to the cursor", cmd_teleport);
+}
+
+fn cmd_teleport(ctx: &mut CommandContext, _: &[&str]) -> Result<String, String> {
+    if ctx.am.selected.is_empty() {
+        return Err(String::from("nobody selected"));
+    }
+
+    let inside = ctx.train.tile_index_at(ctx.cursor).is_some();
+    for &idx in ctx.am.selected.iter() {
+        let a = &mut ctx.am.actors[idx];
+        a.sprite.set_position(&ctx.cursor);
+        a.inside_wagon = inside;
+        a.move_seq.clear();
+        a.waypoints.clear();
+    }
+    Ok(format!("teleported {} actors", ctx.am.selected.len()))
 }
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use resource_manager::{TextureManager, TextureId};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AnimState {
+    Idle,
+    Walk,
+    Attack,
+    Die,
+}
+
+pub struct Frame<'a> {
+    pub texture: &'a Texture,
+    pub rect: IntRect,
+}
+
+impl<'a> Frame<'a> {
+    /// A frame showing the whole of `texture`.
+    pub fn whole(texture: &'a Texture) -> Self {
+        let size = texture.get_size();
+        Frame {
+            texture: texture,
+            rect: IntRect::new(0, 0, size.x as i32, size.y as i32),
+        }
+    }
+}
+
+pub struct Clip<'a> {
+    pub frames: Vec<Frame<'a>>,
+    /// Seconds each frame stays on screen.
+    pub frame_time: f32,
+    pub looping: bool,
+}
+
+impl<'a> Clip<'a> {
+    pub fn new(frames: Vec<Frame<'a>>, frame_time: f32, looping: bool) -> Self {
+        Clip {
+            frames: frames,
+            frame_time: frame_time,
+            looping: looping,
+        }
+    }
+
+    /// Cuts `count` frames of `frame_w` x `frame_h` out of a sheet, left to right, top to bottom.
+    pub fn from_sheet(texture: &'a Texture,
+                      frame_w: i32,
+                      frame_h: i32,
+                      count: usize,
+                      frame_time: f32,
+                      looping: bool)
+                      -> Self {
+        let columns = (texture.get_size().x as i32 / frame_w).max(1);
+        let frames = (0..count as i32)
+            .map(|n| {
+                Frame {
+                    texture: texture,
+                    rect: IntRect::new((n % columns) * frame_w, (n / columns) * frame_h, frame_w, frame_h),
+                }
+            })
+            .collect();
+        Clip::new(frames, frame_time, looping)
+    }
+}
+
+pub struct Animation<'a> {
+    clips: HashMap<AnimState, Clip<'a>>,
+    pub state: AnimState,
+    frame: usize,
+    timer: f32,
+    /// Playback speed multiplier.
+    pub speed: f32,
+    /// Which way the unit faces, in degrees relative to its wagon.
+    pub facing: f32,
+    /// Size every frame is drawn at on screen, so frames cut from textures of different
+    /// sizes all come out the same.
+    pub size: f32,
+}
+
+impl<'a> Animation<'a> {
+    pub fn new(size: f32) -> Self {
+        Animation {
+            clips: HashMap::new(),
+            state: AnimState::Idle,
+            frame: 0,
+            timer: 0.,
+            speed: 1.,
+            facing: 0.,
+            size: size,
+        }
+    }
+
+    pub fn add_clip(&mut self, state: AnimState, clip: Clip<'a>) {
+        self.clips.insert(state, clip);
+    }
+
+    /// Switches to `state`. One-shot clips (attack, die) play through before anything else
+    /// can interrupt them, and nothing interrupts dying.
+    pub fn play(&mut self, state: AnimState) {
+        if state == self.state || self.state == AnimState::Die {
+            return;
+        }
+        if !self.is_finished() && !self.clips.get(&self.state).map(|c| c.looping).unwrap_or(true) {
+            return;
+        }
+        if !self.clips.contains_key(&state) {
+            return;
+        }
+
+        self.state = state;
+        self.frame = 0;
+        self.timer = 0.;
+    }
+
+    /// True once a one-shot clip has shown its last frame.
+    pub fn is_finished(&self) -> bool {
+        match self.clips.get(&self.state) {
+            Some(clip) => !clip.looping && self.frame + 1 >= clip.frames.len() && self.timer >= clip.frame_time,
+            None => true,
+        }
+    }
+
+    /// Turns to face along `dir`. The sprites look down the screen when not rotated.
+    pub fn face(&mut self, dir: Vector2f) {
+        if dir.x != 0. || dir.y != 0. {
+            self.facing = dir.y.atan2(dir.x).to_degrees() - 90.;
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let clip = match self.clips.get(&self.state) {
+            Some(clip) => clip,
+            None => return,
+        };
+
+        self.timer += dt * self.speed;
+        while self.timer >= clip.frame_time {
+            if self.frame + 1 < clip.frames.len() {
+                self.frame += 1;
+            } else if clip.looping {
+                self.frame = 0;
+            } else {
+                // hold the last frame
+                break;
+            }
+            self.timer -= clip.frame_time;
+        }
+    }
+
+    /// Puts the current frame on `sprite`, rotated by `base_rotation` plus the facing.
+    pub fn apply(&self, sprite: &mut Sprite<'a>, base_rotation: f32) {
+        if let Some(frame) = self.clips.get(&self.state).and_then(|c| c.frames.get(self.frame)) {
+            sprite.set_texture(frame.texture, false);
+            sprite.set_texture_rect(&frame.rect);
+            sprite.set_origin2f(frame.rect.width as f32 / 2., frame.rect.height as f32 / 2.);
+            sprite.set_scale2f(self.size / frame.rect.width as f32, self.size / frame.rect.height as f32);
+        }
+        sprite.set_rotation(base_rotation + self.facing);
+    }
+}
+
+/// Clips for the crew, `Char0Nm` is the standing frame and `Char0M` the stepping one.
+/// They're drawn `size` across.
+pub fn actor_animation<'a>(tm: &'a TextureManager, size: f32) -> Animation<'a> {
+    let still = tm.get(TextureId::Char0Nm);
+    let moving = tm.get(TextureId::Char0M);
+
+    let mut animation = Animation::new(size);
+    animation.add_clip(AnimState::Idle, Clip::new(vec![Frame::whole(still)], 1., true));
+    animation.add_clip(AnimState::Walk,
+                       Clip::new(vec![Frame::whole(moving), Frame::whole(still)], 0.15, true));
+    animation.add_clip(AnimState::Attack,
+                       Clip::new(vec![Frame::whole(moving), Frame::whole(still)], 0.1, false));
+    animation.add_clip(AnimState::Die, Clip::new(vec![Frame::whole(still)], 0.45, false));
+    animation
+}
+
+/// The enemy texture is a single frame, so all their clips hold it for as long as the action takes.
+pub fn enemy_animation<'a>(tm: &'a TextureManager, size: f32) -> Animation<'a> {
+    let texture = tm.get(TextureId::Enemy);
+
+    let mut animation = Animation::new(size);
+    animation.add_clip(AnimState::Idle, Clip::new(vec![Frame::whole(texture)], 1., true));
+    animation.add_clip(AnimState::Walk, Clip::new(vec![Frame::whole(texture)], 0.3, true));
+    animation.add_clip(AnimState::Attack, Clip::new(vec![Frame::whole(texture)], 0.2, false));
+    animation.add_clip(AnimState::Die, Clip::new(vec![Frame::whole(texture)], 0.45, false));
+    animation
+}
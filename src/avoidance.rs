@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use sfml::system::Vector2f;
+
+use actor::ActorId;
+use pathfinding::{PathfindingGrid, world_to_grid};
+
+/// Units closer than this push each other apart.
+const SEPARATION_RADIUS: f32 = 28.;
+
+/// How long a blocked unit waits before looking for another way around.
+const WAIT_LIMIT: f32 = 1.;
+
+/// Actors keep their id for life, enemies are only known by their index.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum UnitId {
+    Actor(ActorId),
+    Enemy(usize),
+}
+
+/// What a unit should do this frame to stay out of the others' way.
+pub struct Steering {
+    /// The unit this is for.
+    pub id: UnitId,
+    /// Push away from nearby units, added to the movement direction.
+    pub steer: Vector2f,
+    /// The next tile on the path is taken, don't step into it.
+    pub blocked: bool,
+    /// Waited long enough, plan a new path that avoids this tile.
+    pub replan: Option<(i32, i32)>,
+}
+
+/// Keeps units from walking through each other. Every unit holds the tile it stands on
+/// and reserves the next tile on its path, nobody may step into a tile held by someone else.
+pub struct Avoidance {
+    claims: HashMap<(i32, i32), UnitId>,
+    waiting: HashMap<UnitId, f32>,
+}
+
+impl Avoidance {
+    pub fn new() -> Self {
+        Avoidance {
+            claims: HashMap::new(),
+            waiting: HashMap::new(),
+        }
+    }
+
+    /// `units` holds every unit's id, position and next path step, `grid` is only used to map positions to tiles.
+    pub fn update(&mut self,
+                  units: &[(UnitId, Vector2f, Option<Vector2f>)],
+                  grid: &PathfindingGrid,
+                  train_pos: Vector2f,
+                  dt: f32)
+                  -> Vec<Steering> {
+        self.claims.clear();
+        for &(id, pos, _) in units.iter() {
+            self.claims.entry(world_to_grid(pos, grid, train_pos)).or_insert(id);
+        }
+
+        let mut result = Vec::with_capacity(units.len());
+        for &(id, pos, next) in units.iter() {
+            let mut blocked = None;
+            if let Some(next) = next {
+                let tile = world_to_grid(next, grid, train_pos);
+                let holder = *self.claims.entry(tile).or_insert(id);
+                if holder != id {
+                    blocked = Some(tile);
+                }
+            }
+
+            let mut replan = None;
+            match blocked {
+                Some(tile) => {
+                    let waited = self.waiting.entry(id).or_insert(0.);
+                    *waited += dt;
+                    if *waited >= WAIT_LIMIT {
+                        *waited = 0.;
+                        replan = Some(tile);
+                    }
+                }
+                None => {
+                    self.waiting.remove(&id);
+                }
+            }
+
+            result.push(Steering {
+                id: id,
+                steer: separation(pos, id, units),
+                blocked: blocked.is_some(),
+                replan: replan,
+            });
+        }
+        result
+    }
+
+    /// Forgets how long these actors have waited, they're gone.
+    pub fn forget_actors(&mut self, ids: &[ActorId]) {
+        self.waiting.retain(|id, _| match *id {
+            UnitId::Actor(a) => !ids.contains(&a),
+            UnitId::Enemy(_) => true,
+        });
+    }
+
+    /// Forgets how long every enemy has waited, needed whenever enemy indices shift.
+    pub fn forget_enemies(&mut self) {
+        self.waiting.retain(|id, _| match *id {
+            UnitId::Actor(_) => true,
+            UnitId::Enemy(_) => false,
+        });
+    }
+}
+
+/// Sum of pushes away from every unit within `SEPARATION_RADIUS`, stronger the closer they are.
+fn separation(pos: Vector2f, id: UnitId, units: &[(UnitId, Vector2f, Option<Vector2f>)]) -> Vector2f {
+    let mut push = Vector2f::new(0., 0.);
+    for &(other_id, other, _) in units.iter() {
+        if other_id == id {
+            continue;
+        }
+
+        let (dx, dy) = (pos.x - other.x, pos.y - other.y);
+        let d = (dx * dx + dy * dy).sqrt();
+        if d >= SEPARATION_RADIUS {
+            continue;
+        }
+
+        if d < 0.01 {
+            // exactly on top of each other, split them up along x by id order
+            push.x += if id < other_id { -1. } else { 1. };
+        } else {
+            let strength = (SEPARATION_RADIUS - d) / SEPARATION_RADIUS;
+            push.x += dx / d * strength;
+            push.y += dy / d * strength;
+        }
+    }
+    push
+}
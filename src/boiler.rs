@@ -0,0 +1,74 @@
+/// How much coal fits in the firebox.
+pub const FIREBOX_CAPACITY: f32 = 20.;
+
+/// Coal burnt per second while the fire is lit.
+const BURN_RATE: f32 = 0.2;
+
+/// Pressure gained per unit of coal burnt.
+const PRESSURE_PER_COAL: f32 = 60.;
+
+/// Pressure lost per second through the safety valve and leaks.
+const PRESSURE_LEAK: f32 = 4.;
+
+/// Extra pressure used per second when pulling at full effort.
+const PRESSURE_USE: f32 = 6.;
+
+pub const MAX_PRESSURE: f32 = 200.;
+
+/// Below this there isn't enough steam to move at all.
+const MIN_WORKING_PRESSURE: f32 = 20.;
+
+/// Coal shovelled into the firebox per second by a fully rested stoker.
+pub const STOKE_RATE: f32 = 2.;
+
+pub struct Boiler {
+    pub firebox: f32,
+    pub pressure: f32,
+}
+
+impl Boiler {
+    pub fn new() -> Self {
+        Boiler {
+            firebox: 0.,
+            pressure: 0.,
+        }
+    }
+
+    /// Puts up to `amount` coal in the firebox, returns how much was accepted.
+    pub fn stoke(&mut self, amount: f32) -> f32 {
+        let accepted = amount.min(FIREBOX_CAPACITY - self.firebox).max(0.);
+        self.firebox += accepted;
+        accepted
+    }
+
+    /// Coal the firebox can still take.
+    pub fn free_space(&self) -> f32 {
+        (FIREBOX_CAPACITY - self.firebox).max(0.)
+    }
+
+    pub fn update(&mut self, dt: f32, pulling: bool) {
+        let burnt = self.firebox.min(BURN_RATE * dt);
+        self.firebox -= burnt;
+        self.pressure += burnt * PRESSURE_PER_COAL;
+
+        self.pressure -= PRESSURE_LEAK * dt;
+        if pulling {
+            self.pressure -= PRESSURE_USE * self.tractive_effort() * dt;
+        }
+
+        self.pressure = self.pressure.max(0.).min(MAX_PRESSURE);
+    }
+
+    /// Fraction of full tractive effort the current pressure can provide, 0 to 1.
+    pub fn tractive_effort(&self) -> f32 {
+        if self.pressure < MIN_WORKING_PRESSURE {
+            0.
+        } else {
+            (self.pressure - MIN_WORKING_PRESSURE) / (MAX_PRESSURE - MIN_WORKING_PRESSURE)
+        }
+    }
+
+    pub fn is_dry(&self) -> bool {
+        self.firebox <= 0.
+    }
+}
@@ -1,28 +1,119 @@
-use sfml::graphics::View;
-use sfml::system::Vector2f;
+use sfml::graphics::{View, FloatRect};
+use sfml::system::{Vector2f, Vector2u};
+
+use replay::InputState;
+use actor::ActorId;
+use game_consts::*;
+
+/// How far the view can zoom in and out, relative to the window size.
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 3.;
+
+/// How quickly the view catches up with its target, higher is snappier.
+const SMOOTHING: f32 = 6.;
+
+/// Keyboard and edge panning speed in screen pixels per second.
+const PAN_SPEED: f32 = 900.;
+
+/// The cursor pans the view when it's this close to the window border.
+const EDGE_MARGIN: i32 = 8;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CameraMode {
+    Free,
+    FollowTrain,
+    FollowUnit(ActorId),
+}
 
 pub struct Camera {
     pub game: View,
     pub ui: View,
     zoom_step: f32,
     pub mouse_pos_old: Vector2f,
+    pub mode: CameraMode,
+    /// Turn the view along with the train on curves.
+    pub rotate_with_train: bool,
+    /// Where the view is heading, it eases towards this every update.
+    target: Vector2f,
+    zoom_level: f32,
+    base_size: Vector2f,
 }
 
 impl Camera {
     pub fn new() -> Self {
         Camera {
             game: View::new().unwrap(),
-            ui: View::new_init(&Vector2f::new(1600. / 2., 1200. / 2.), &Vector2f::new(1600., 1200.)).unwrap(),
+            ui: View::new_init(&Vector2f::new(WINDOW_SIZE_X as f32 / 2., WINDOW_SIZE_Y as f32 / 2.),
+                               &Vector2f::new(WINDOW_SIZE_X as f32, WINDOW_SIZE_Y as f32))
+                .unwrap(),
             zoom_step: 0.1,
             mouse_pos_old: Vector2f::new(0., 0.),
+            mode: CameraMode::FollowTrain,
+            rotate_with_train: false,
+            target: Vector2f::new(0., 0.),
+            zoom_level: 1.,
+            base_size: Vector2f::new(0., 0.),
         }
     }
 
+    /// Starts from `view`, which is what zoom level 1 looks like.
+    pub fn init(&mut self, view: View) {
+        self.base_size = view.get_size();
+        self.target = view.get_center();
+        self.zoom_level = 1.;
+        self.game = view;
+    }
+
+    /// Fits both views to a window of `width` x `height` pixels. The game view shows more
+    /// of the world in a bigger window, the ui keeps its size and gets letterboxed.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.base_size = Vector2f::new(width as f32, height as f32);
+        self.game.set_size2f(self.base_size.x * self.zoom_level, self.base_size.y * self.zoom_level);
+
+        let scale_x = width as f32 / WINDOW_SIZE_X as f32;
+        let scale_y = height as f32 / WINDOW_SIZE_Y as f32;
+        let viewport = if scale_x > scale_y {
+            // too wide, bars left and right
+            let w = scale_y / scale_x;
+            FloatRect::new((1. - w) / 2., 0., w, 1.)
+        } else {
+            let h = scale_x / scale_y;
+            FloatRect::new(0., (1. - h) / 2., 1., h)
+        };
+        self.ui.set_viewport(&viewport);
+    }
+
     pub fn move_by_mouse(&mut self, mouse_pos: &Vector2f) {
         let move_factor = Vector2f::new(self.mouse_pos_old.x - mouse_pos.x,
                                         self.mouse_pos_old.y - mouse_pos.y);
 
         self.game.move_(&move_factor);
+
+        // dragging takes over from any follow mode
+        self.mode = CameraMode::Free;
+        self.target = self.game.get_center();
+    }
+
+    /// The part of the world the game view shows, ignoring rotation.
+    pub fn visible_rect(&self) -> FloatRect {
+        let center = self.game.get_center();
+        let size = self.game.get_size();
+        FloatRect::new(center.x - size.x / 2., center.y - size.y / 2., size.x, size.y)
+    }
+
+    /// Glides over to `pos` and stays there.
+    pub fn center_on(&mut self, pos: &Vector2f) {
+        self.mode = CameraMode::Free;
+        self.target = *pos;
+    }
+
+    /// Free, then following the train, then following `unit` if there is one.
+    pub fn cycle_mode(&mut self, unit: Option<ActorId>) {
+        self.mode = match (self.mode, unit) {
+            (CameraMode::Free, _) => CameraMode::FollowTrain,
+            (CameraMode::FollowTrain, Some(id)) => CameraMode::FollowUnit(id),
+            _ => CameraMode::Free,
+        };
     }
 
     pub fn zoom(&mut self, delta: i32) {
@@ -31,6 +122,66 @@ impl Camera {
             false => 1.0 - self.zoom_step,
         };
 
-        self.game.zoom(zoom_factor);
+        self.zoom_level = (self.zoom_level * zoom_factor).max(MIN_ZOOM).min(MAX_ZOOM);
+        self.game.set_size2f(self.base_size.x * self.zoom_level, self.base_size.y * self.zoom_level);
+    }
+
+    /// Which way the arrow keys and the window edges push the view, in screen directions.
+    pub fn pan_input(input: &InputState, window_size: Vector2u) -> Vector2f {
+        let mut pan = Vector2f::new(input.arrows.x as f32, input.arrows.y as f32);
+        let mouse = input.mouse;
+
+        // only while the cursor is actually inside the window
+        let (w, h) = (window_size.x as i32, window_size.y as i32);
+        if mouse.x >= 0 && mouse.y >= 0 && mouse.x < w && mouse.y < h {
+            if mouse.x < EDGE_MARGIN {
+                pan.x -= 1.;
+            } else if mouse.x >= w - EDGE_MARGIN {
+                pan.x += 1.;
+            }
+            if mouse.y < EDGE_MARGIN {
+                pan.y -= 1.;
+            } else if mouse.y >= h - EDGE_MARGIN {
+                pan.y += 1.;
+            }
+        }
+        pan
+    }
+
+    /// `follow` is the position of whatever the current mode follows, `rotation` the train's.
+    /// Any panning drops out of the follow modes.
+    pub fn update(&mut self, dt: f32, follow: Option<Vector2f>, rotation: f32, pan: Vector2f) {
+        if pan.x != 0. || pan.y != 0. {
+            self.mode = CameraMode::Free;
+
+            // pan along the screen, which isn't the world when the view is turned
+            let angle = self.game.get_rotation().to_radians();
+            let speed = PAN_SPEED * self.zoom_level * dt;
+            self.target.x += (pan.x * angle.cos() - pan.y * angle.sin()) * speed;
+            self.target.y += (pan.x * angle.sin() + pan.y * angle.cos()) * speed;
+        }
+
+        match (self.mode, follow) {
+            (CameraMode::Free, _) => {}
+            (_, Some(pos)) => self.target = pos,
+            // whatever we followed is gone
+            (_, None) => self.mode = CameraMode::Free,
+        }
+
+        let t = (SMOOTHING * dt).min(1.);
+        let center = self.game.get_center();
+        self.game.set_center2f(center.x + (self.target.x - center.x) * t,
+                               center.y + (self.target.y - center.y) * t);
+
+        let goal = if self.rotate_with_train { rotation } else { 0. };
+        let current = self.game.get_rotation();
+        // take the short way round
+        let mut diff = (goal - current) % 360.;
+        if diff > 180. {
+            diff -= 360.;
+        } else if diff < -180. {
+            diff += 360.;
+        }
+        self.game.set_rotation(current + diff * t);
     }
 }
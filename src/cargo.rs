@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use resource_manager::{FontManager, FontId};
+use train::Train;
+
+/// How many items an actor can carry at once.
+pub const CARRY_CAPACITY: u32 = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ItemType {
+    Coal,
+    Ammo,
+    Food,
+    Parts,
+}
+
+impl ItemType {
+    pub fn all() -> [ItemType; 4] {
+        [ItemType::Coal, ItemType::Ammo, ItemType::Food, ItemType::Parts]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ItemType::Coal => "COAL",
+            ItemType::Ammo => "AMMO",
+            ItemType::Food => "FOOD",
+            ItemType::Parts => "PARTS",
+        }
+    }
+
+    pub fn next(&self) -> ItemType {
+        match *self {
+            ItemType::Coal => ItemType::Ammo,
+            ItemType::Ammo => ItemType::Food,
+            ItemType::Food => ItemType::Parts,
+            ItemType::Parts => ItemType::Coal,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Storage {
+    pub capacity: u32,
+    pub items: HashMap<ItemType, u32>,
+}
+
+impl Storage {
+    pub fn new(capacity: u32) -> Self {
+        Storage {
+            capacity: capacity,
+            items: HashMap::new(),
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.items.values().sum()
+    }
+
+    pub fn free_space(&self) -> u32 {
+        self.capacity - self.total()
+    }
+
+    pub fn count(&self, item: ItemType) -> u32 {
+        *self.items.get(&item).unwrap_or(&0)
+    }
+
+    /// Stores as much of `amount` as fits and returns how many were actually stored.
+    pub fn add(&mut self, item: ItemType, amount: u32) -> u32 {
+        let added = amount.min(self.free_space());
+        if added > 0 {
+            *self.items.entry(item).or_insert(0) += added;
+        }
+        added
+    }
+
+    /// Takes up to `amount` of `item` and returns how many were actually taken.
+    pub fn take(&mut self, item: ItemType, amount: u32) -> u32 {
+        let taken = amount.min(self.count(item));
+        if taken > 0 {
+            *self.items.get_mut(&item).unwrap() -= taken;
+        }
+        taken
+    }
+
+    pub fn most_plentiful(&self) -> Option<ItemType> {
+        self.items
+            .iter()
+            .filter(|&(_, n)| *n > 0)
+            .max_by_key(|&(_, n)| *n)
+            .map(|(item, _)| *item)
+    }
+}
+
+/// What an actor does once it reaches its destination tile.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Task {
+    /// Pick up from or deposit into the storage at `(wagon, row, column)`.
+    Haul((usize, usize, usize)),
+    /// Shovel carried coal into the firebox at `(wagon, row, column)`.
+    Stoke((usize, usize, usize)),
+}
+
+/// Picks up `preferred` (or whatever there is most of) if `carrying` is empty, deposits otherwise.
+pub fn haul(carrying: &mut Option<(ItemType, u32)>, storage: &mut Storage, preferred: ItemType) {
+    match *carrying {
+        Some((item, amount)) => {
+            let left = amount - storage.add(item, amount);
+            *carrying = if left > 0 { Some((item, left)) } else { None };
+        }
+        None => {
+            let item = if storage.count(preferred) > 0 {
+                Some(preferred)
+            } else {
+                storage.most_plentiful()
+            };
+
+            if let Some(item) = item {
+                let taken = storage.take(item, CARRY_CAPACITY);
+                *carrying = Some((item, taken));
+            }
+        }
+    }
+}
+
+/// Sums up everything held in the train's storage tiles.
+pub fn manifest(train: &Train) -> (HashMap<ItemType, u32>, u32) {
+    let mut totals = HashMap::new();
+    let mut capacity = 0;
+    for w in train.wagons.iter() {
+        for t in w.tiles.iter() {
+            for t in t.iter() {
+                if let Some(ref storage) = t.storage {
+                    capacity += storage.capacity;
+                    for (item, n) in storage.items.iter() {
+                        *totals.entry(*item).or_insert(0) += *n;
+                    }
+                }
+            }
+        }
+    }
+    (totals, capacity)
+}
+
+/// Items that still fit in the train's storage tiles.
+pub fn free_space(train: &Train) -> u32 {
+    let (totals, capacity) = manifest(train);
+    capacity - totals.values().sum::<u32>()
+}
+
+/// Spreads `amount` of `item` over the train's storage tiles, returns how many fit.
+pub fn load(train: &mut Train, item: ItemType, amount: u32) -> u32 {
+    let mut loaded = 0;
+    for w in train.wagons.iter_mut() {
+        for t in w.tiles.iter_mut() {
+            for t in t.iter_mut() {
+                if let Some(ref mut storage) = t.storage {
+                    loaded += storage.add(item, amount - loaded);
+                }
+            }
+        }
+    }
+    loaded
+}
+
+/// Takes up to `amount` of `item` out of the train's storage tiles, returns how many were taken.
+pub fn unload(train: &mut Train, item: ItemType, amount: u32) -> u32 {
+    let mut unloaded = 0;
+    for w in train.wagons.iter_mut() {
+        for t in w.tiles.iter_mut() {
+            for t in t.iter_mut() {
+                if let Some(ref mut storage) = t.storage {
+                    unloaded += storage.take(item, amount - unloaded);
+                }
+            }
+        }
+    }
+    unloaded
+}
+
+pub struct ManifestPanel<'a> {
+    background: RectangleShape<'a>,
+    text: Text<'a>,
+    pub visible: bool,
+}
+
+impl<'a> ManifestPanel<'a> {
+    pub fn new() -> Self {
+        let mut background = RectangleShape::new().unwrap();
+        background.set_position2f(20., 20.);
+        background.set_size2f(360., 230.);
+        background.set_fill_color(&Color::new_rgba(0, 0, 0, 170));
+        background.set_outline_color(&Color::white());
+        background.set_outline_thickness(2.);
+
+        ManifestPanel {
+            background: background,
+            text: Text::new().unwrap(),
+            visible: true,
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager) {
+        self.text.set_font(fm.get(FontId::Joystix));
+        self.text.set_character_size(20);
+        self.text.set_position(&Vector2f::new(35., 30.));
+    }
+
+    pub fn update(&mut self, train: &Train, carried: u32, hauling: ItemType) {
+        let (totals, capacity) = manifest(train);
+
+        let mut s = String::from("CARGO MANIFEST\n");
+        let mut stored = 0;
+        for item in ItemType::all().iter() {
+            let n = *totals.get(item).unwrap_or(&0);
+            stored += n;
+            s.push_str(&format!("{:<6} {:>4}\n", item.name(), n));
+        }
+        s.push_str(&format!("STORED {}/{}\n", stored, capacity));
+        s.push_str(&format!("CARRIED {}\n", carried));
+        s.push_str(&format!("HAULING {}", hauling.name()));
+
+        self.text.set_string(&s);
+    }
+
+    pub fn draw(&self, window: &mut RenderWindow) {
+        if self.visible {
+            window.draw(&self.background);
+            window.draw(&self.text);
+        }
+    }
+}
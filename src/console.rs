@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use resource_manager::{FontManager, FontId, TextureManager};
+use train::Train;
+use enemy::Enemy;
+use actor_manager::ActorManager;
+use station::StationManager;
+use scripting::Scripting;
+use game_consts::*;
+
+/// How many lines of output the console keeps on screen.
+const SHOWN_LINES: usize = 12;
+const LINE_HEIGHT: f32 = 20.;
+
+/// What a console command gets to work with.
+pub struct CommandContext<'c, 'a: 'c> {
+    pub train: &'c mut Train<'a>,
+    pub enemies: &'c mut Vec<Enemy<'a>>,
+    pub am: &'c mut ActorManager<'a>,
+    pub stations: &'c StationManager<'a>,
+    pub scripting: &'c mut Scripting<'a>,
+    pub tm: &'a TextureManager,
+    /// The cursor in world coordinates.
+    pub cursor: Vector2f,
+    pub time_scale: &'c mut f32,
+}
+
+/// A command gets its arguments without the command name, `Ok` is printed as is and `Err` as an error.
+pub type Command = for<'c, 'a> fn(&mut CommandContext<'c, 'a>, &[&str]) -> Result<String, String>;
+
+/// Every command the console knows. Each subsystem registers its own through a
+/// `register_commands` function so they live next to the code they poke at.
+pub struct CommandRegistry {
+    commands: BTreeMap<&'static str, (Command, &'static str)>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        CommandRegistry { commands: BTreeMap::new() }
+    }
+
+    pub fn register(&mut self, name: &'static str, help: &'static str, command: Command) {
+        if self.commands.insert(name, (command, help)).is_some() {
+            println!("console command {} registered twice", name);
+        }
+    }
+
+    pub fn help(&self) -> String {
+        let mut lines = vec![String::from("help - list commands"), String::from("clear - clear the console")];
+        lines.extend(self.commands.iter().map(|(name, &(_, help))| format!("{} - {}", name, help)));
+        lines.join("\n")
+    }
+
+    pub fn run(&self, line: &str, ctx: &mut CommandContext) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let name = match words.first() {
+            Some(name) => *name,
+            None => return Ok(String::new()),
+        };
+
+        if name == "help" {
+            return Ok(self.help());
+        }
+
+        match self.commands.get(name) {
+            Some(&(command, _)) => command(ctx, &words[1..]),
+            None => Err(format!("unknown command {}, try help", name)),
+        }
+    }
+}
+
+/// Parses the `n`th argument, naming it in the error.
+pub fn arg<T: ::std::str::FromStr>(args: &[&str], n: usize, name: &str) -> Result<T, String> {
+    match args.get(n) {
+        Some(s) => s.parse().map_err(|_| format!("bad {}: {}", name, s)),
+        None => Err(format!("missing {}", name)),
+    }
+}
+
+/// Drop-down console, opened with the tilde key.
+pub struct Console<'a> {
+    pub registry: CommandRegistry,
+    pub visible: bool,
+    input: String,
+    output: Vec<String>,
+    /// Commands entered so far, most recent last.
+    history: Vec<String>,
+    background: RectangleShape<'a>,
+    text: Text<'a>,
+}
+
+impl<'a> Console<'a> {
+    pub fn new() -> Self {
+        let mut background = RectangleShape::new().unwrap();
+        background.set_position2f(0., 0.);
+        background.set_size2f(WINDOW_SIZE_X as f32, (SHOWN_LINES + 2) as f32 * LINE_HEIGHT);
+        background.set_fill_color(&Color::new_rgba(0, 0, 0, 220));
+        background.set_outline_color(&Color::green());
+        background.set_outline_thickness(1.);
+
+        Console {
+            registry: CommandRegistry::new(),
+            visible: false,
+            input: String::new(),
+            output: vec![],
+            history: vec![],
+            background: background,
+            text: Text::new().unwrap(),
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager) {
+        self.text.set_font(fm.get(FontId::Arial));
+        self.text.set_character_size(16);
+        self.text.set_color(&Color::green());
+        self.text.set_position2f(10., 4.);
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.input.clear();
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        // the tilde key opens and closes us, don't type it
+        if !c.is_control() && c != '`' && c != '~' {
+            self.input.push(c);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    /// Brings back the last command entered.
+    pub fn recall(&mut self) {
+        if let Some(last) = self.history.last() {
+            self.input = last.clone();
+        }
+    }
+
+    fn print(&mut self, s: &str) {
+        for line in s.lines() {
+            self.output.push(String::from(line));
+        }
+    }
+
+    /// Runs whatever has been typed in.
+    pub fn submit(&mut self, ctx: &mut CommandContext) {
+        let line = self.input.trim().to_string();
+        self.input.clear();
+        if line.is_empty() {
+            return;
+        }
+
+        self.print(&format!("> {}", line));
+        if line == "clear" {
+            self.output.clear();
+        } else {
+            match self.registry.run(&line, ctx) {
+                Ok(out) => self.print(&out),
+                Err(err) => self.print(&format!("error: {}", err)),
+            }
+        }
+        self.history.push(line);
+    }
+
+    /// Draws the console, call with the ui view set.
+    pub fn draw(&mut self, window: &mut RenderWindow) {
+        if !self.visible {
+            return;
+        }
+
+        // newest output sits right above the input line
+        let input = format!("> {}_", self.input);
+        let first = self.output.len().saturating_sub(SHOWN_LINES);
+        let mut lines = vec![""; SHOWN_LINES - (self.output.len() - first)];
+        lines.extend(self.output[first..].iter().map(|l| l.as_str()));
+        lines.push(&input);
+        self.text.set_string(&lines.join("\n"));
+
+        window.draw(&self.background);
+        window.draw(&self.text);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+use sfml::window::Key;
+
+use resource_manager::{FontManager, FontId};
+use pathfinding::{PathfindingGrid, grid_to_world};
+use train::Train;
+use actor::Actor;
+use enemy::Enemy;
+use game_consts::*;
+
+/// Developer overlays, only built into debug builds.
+///
+/// F1/F2/F3 show the inside, outside and combined pathfinding grids, F4 the tile collision bounds,
+/// F5 every unit's path and `inside_wagon` flag, F6 frame time and fps.
+pub struct DebugOverlays<'a> {
+    pf_in: bool,
+    pf_out: bool,
+    pf_all: bool,
+    bounds: bool,
+    units: bool,
+    stats: bool,
+    /// Smoothed frame time in seconds.
+    frame_time: f32,
+    label: Text<'a>,
+    stats_text: Text<'a>,
+}
+
+impl<'a> DebugOverlays<'a> {
+    pub fn new() -> Self {
+        DebugOverlays {
+            pf_in: false,
+            pf_out: false,
+            pf_all: false,
+            bounds: false,
+            units: false,
+            stats: false,
+            frame_time: 0.,
+            label: Text::new().unwrap(),
+            stats_text: Text::new().unwrap(),
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager) {
+        self.label.set_font(fm.get(FontId::Joystix));
+        self.label.set_character_size(10);
+        self.stats_text.set_font(fm.get(FontId::Joystix));
+        self.stats_text.set_character_size(16);
+        self.stats_text.set_color(&Color::cyan());
+        self.stats_text.set_position2f(20., 400.);
+    }
+
+    /// Flips the overlay bound to `code`, false if it isn't one of ours.
+    pub fn toggle(&mut self, code: Key) -> bool {
+        let flag = match code {
+            Key::F1 => &mut self.pf_in,
+            Key::F2 => &mut self.pf_out,
+            Key::F3 => &mut self.pf_all,
+            Key::F4 => &mut self.bounds,
+            Key::F5 => &mut self.units,
+            Key::F6 => &mut self.stats,
+            _ => return false,
+        };
+        *flag = !*flag;
+        true
+    }
+
+    /// `dt` is the real frame time, paused or not.
+    pub fn update(&mut self, dt: f32) {
+        self.frame_time = if self.frame_time == 0. { dt } else { self.frame_time * 0.9 + dt * 0.1 };
+
+        if self.stats {
+            let fps = if self.frame_time > 0. { 1. / self.frame_time } else { 0. };
+            self.stats_text.set_string(&format!("{:.2} MS\n{:.0} FPS", self.frame_time * 1000., fps));
+        }
+    }
+
+    /// Draws the overlays that live in the world, call with the game view set.
+    pub fn draw_world(&mut self, window: &mut RenderWindow, train: &Train, actors: &[Actor], enemies: &[Enemy]) {
+        let train_pos = train.get_origin();
+        if self.pf_all {
+            draw_grid(window, &train.pfgrid_all, train_pos, &Color::new_rgba(0, 255, 0, 60));
+        }
+        if self.pf_out {
+            draw_grid(window, &train.pfgrid_out, train_pos, &Color::new_rgba(0, 0, 255, 60));
+        }
+        if self.pf_in {
+            draw_grid(window, &train.pfgrid_in, train_pos, &Color::new_rgba(0, 255, 255, 60));
+        }
+
+        if self.bounds {
+            draw_bounds(window, train);
+        }
+
+        if self.units {
+            for a in actors.iter() {
+                self.draw_unit(window, a.sprite.get_position(), a.inside_wagon, &a.move_seq, &Color::green());
+            }
+            for e in enemies.iter() {
+                self.draw_unit(window, e.sprite.get_position(), e.inside_wagon, &e.move_seq, &Color::magenta());
+            }
+        }
+    }
+
+    fn draw_unit(&mut self,
+                 window: &mut RenderWindow,
+                 pos: Vector2f,
+                 inside_wagon: bool,
+                 path: &VecDeque<Vector2f>,
+                 color: &Color) {
+        let mut va = VertexArray::new().unwrap();
+        va.set_primitive_type(PrimitiveType::sfLinesStrip);
+        va.append(&Vertex::new_with_pos_color(&pos, color));
+        for step in path.iter() {
+            va.append(&Vertex::new_with_pos_color(step, color));
+        }
+        window.draw(&va);
+
+        self.label.set_string(if inside_wagon { "IN" } else { "OUT" });
+        self.label.set_color(color);
+        self.label.set_position2f(pos.x + 12., pos.y - 24.);
+        window.draw(&self.label);
+    }
+
+    /// Draws the overlays that live on the screen, call with the ui view set.
+    pub fn draw_ui(&self, window: &mut RenderWindow) {
+        if self.stats {
+            window.draw(&self.stats_text);
+        }
+    }
+}
+
+/// Walkable tiles in `color`, blocked ones in red.
+fn draw_grid(window: &mut RenderWindow, grid: &PathfindingGrid, train_pos: Vector2f, color: &Color) {
+    let mut shape = RectangleShape::new().unwrap();
+    shape.set_size2f(TILE_SIZE_X as f32 - 2., TILE_SIZE_Y as f32 - 2.);
+    shape.set_origin2f(TILE_SIZE_X as f32 / 2. - 1., TILE_SIZE_Y as f32 / 2. - 1.);
+
+    for (i, column) in grid.grid.iter().enumerate() {
+        for (j, t) in column.iter().enumerate() {
+            shape.set_position(&grid_to_world((i as i32, j as i32), grid, train_pos));
+            if t.walkable {
+                shape.set_fill_color(color);
+            } else {
+                shape.set_fill_color(&Color::new_rgba(255, 0, 0, 60));
+            }
+            window.draw(&shape);
+        }
+    }
+}
+
+/// Collision boxes of solid tiles, exactly the rects movement checks against.
+fn draw_bounds(window: &mut RenderWindow, train: &Train) {
+    let mut shape = RectangleShape::new().unwrap();
+    shape.set_fill_color(&Color::new_rgba(0, 0, 255, 100));
+
+    for w in train.wagons.iter() {
+        for t in w.tiles.iter().flat_map(|row| row.iter()).filter(|t| t.is_solid) {
+            for b in t.bounds.iter().filter_map(|b| *b) {
+                let rect = t.collision_box(&b);
+                shape.set_size2f(rect.width, rect.height);
+                shape.set_position2f(rect.left, rect.top);
+                window.draw(&shape);
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+extern crate rand;
+
+use rand::Rng;
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use resource_manager::{FontManager, FontId};
+use cargo::{self, ItemType};
+use station::StationManager;
+use train::Train;
+use rng::GameRng;
+
+/// How many offers a station puts up at once.
+const OFFERS_PER_STATION: usize = 3;
+
+/// How many passengers fit in the train.
+pub const PASSENGER_CAPACITY: u32 = 8;
+
+/// What a coal unit costs at a station.
+pub const COAL_PRICE: i32 = 2;
+
+/// How many of the latest notices the journal shows.
+const NOTICES_SHOWN: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Freight {
+    Items(ItemType, u32),
+    Passengers(u32),
+}
+
+impl Freight {
+    pub fn describe(&self) -> String {
+        match *self {
+            Freight::Items(item, n) => format!("{} {}", n, item.name()),
+            Freight::Passengers(n) => format!("{} PASSENGERS", n),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ContractState {
+    Offered,
+    Active,
+    Paid,
+    Failed,
+}
+
+#[derive(Clone)]
+pub struct Contract {
+    /// Indices into `StationManager::stations`.
+    pub from: usize,
+    pub to: usize,
+    pub freight: Freight,
+    /// Game time in seconds by which the freight must arrive.
+    pub deadline: f32,
+    pub payout: i32,
+    pub state: ContractState,
+}
+
+pub struct Economy {
+    pub money: i32,
+    /// Game time in seconds, used for deadlines.
+    pub time: f32,
+    pub contracts: Vec<Contract>,
+    pub passengers: u32,
+    /// Which of the current offers is highlighted in the journal.
+    pub highlighted: usize,
+    /// Station we last generated offers at, so we only do it once per visit.
+    offers_made_at: Option<usize>,
+    /// Latest news for the player, contracts and crew, newest last, shown in the journal.
+    pub notices: Vec<String>,
+}
+
+impl Economy {
+    pub fn new(money: i32) -> Self {
+        Economy {
+            money: money,
+            time: 0.,
+            contracts: vec![],
+            passengers: 0,
+            highlighted: 0,
+            offers_made_at: None,
+            notices: vec![],
+        }
+    }
+
+    /// Puts a line in the journal, old ones drop off.
+    pub fn notify(&mut self, notice: String) {
+        self.notices.push(notice);
+        if self.notices.len() > NOTICES_SHOWN {
+            self.notices.remove(0);
+        }
+    }
+
+    pub fn update(&mut self, dt: f32, stations: &StationManager, train: &mut Train, rng: &mut GameRng) {
+        self.time += dt;
+
+        let mut failed = vec![];
+        for c in self.contracts.iter_mut() {
+            if c.state == ContractState::Active && self.time > c.deadline {
+                c.state = ContractState::Failed;
+                failed.push(c.clone());
+            }
+        }
+        for c in failed {
+            self.fail(&c, stations, train);
+        }
+
+        match stations.docked {
+            Some(idx) => {
+                if self.offers_made_at != Some(idx) {
+                    self.offers_made_at = Some(idx);
+                    self.deliver(idx, stations, train);
+                    self.make_offers(idx, stations, rng);
+                }
+            }
+            None => {
+                // offers don't follow us out of the station
+                self.contracts.retain(|c| c.state != ContractState::Offered);
+                self.offers_made_at = None;
+                self.highlighted = 0;
+            }
+        }
+    }
+
+    fn make_offers(&mut self, from: usize, stations: &StationManager, rng: &mut GameRng) {
+        let destinations = stations.stations.len() - from - 1;
+        if destinations == 0 {
+            return;
+        }
+
+        for _ in 0..OFFERS_PER_STATION {
+            let to = from + 1 + rng.gen_range(0, destinations);
+            let freight = if rng.gen_weighted_bool(3) {
+                Freight::Passengers(rng.gen_range(1, 5))
+            } else {
+                let items = ItemType::all();
+                Freight::Items(items[rng.gen_range(0, items.len())], rng.gen_range(5, 30))
+            };
+
+            let distance = stations.stations[to].position - stations.stations[from].position;
+            self.contracts.push(Contract {
+                from: from,
+                to: to,
+                freight: freight,
+                // a slow but steady train should make it
+                deadline: self.time + 60. + distance / 100.,
+                payout: 50 + (distance / 100.) as i32,
+                state: ContractState::Offered,
+            });
+        }
+    }
+
+    /// Takes the freight of a failed contract off the train, nobody pays for it and the
+    /// player doesn't get to keep it.
+    fn fail(&mut self, c: &Contract, stations: &StationManager, train: &mut Train) {
+        match c.freight {
+            // written off, whatever is left of it goes back to the shipper
+            Freight::Items(item, n) => {
+                cargo::unload(train, item, n);
+            }
+            // late passengers give up and leave without paying
+            Freight::Passengers(n) => self.passengers -= n,
+        }
+        self.notify(format!("FAILED: {} TO {}", c.freight.describe(), stations.stations[c.to].name));
+    }
+
+    /// Hands over the freight of every active contract ending at station `at`.
+    fn deliver(&mut self, at: usize, stations: &StationManager, train: &mut Train) {
+        for idx in 0..self.contracts.len() {
+            let c = self.contracts[idx].clone();
+            if c.state != ContractState::Active || c.to != at {
+                continue;
+            }
+
+            let delivered = match c.freight {
+                Freight::Items(item, n) => {
+                    if cargo::manifest(train).0.get(&item).cloned().unwrap_or(0) >= n {
+                        cargo::unload(train, item, n);
+                        true
+                    } else {
+                        false
+                    }
+                }
+                Freight::Passengers(n) => {
+                    self.passengers -= n;
+                    true
+                }
+            };
+
+            if delivered {
+                self.money += c.payout;
+                self.contracts[idx].state = ContractState::Paid;
+                self.notify(format!("DELIVERED {} TO {} +${}", c.freight.describe(), stations.stations[at].name, c.payout));
+            } else {
+                self.contracts[idx].state = ContractState::Failed;
+                self.fail(&c, stations, train);
+            }
+        }
+    }
+
+    pub fn offers(&self) -> usize {
+        self.contracts.iter().filter(|c| c.state == ContractState::Offered).count()
+    }
+
+    pub fn highlight_next(&mut self) {
+        let offers = self.offers();
+        if offers > 0 {
+            self.highlighted = (self.highlighted + 1) % offers;
+        }
+    }
+
+    /// Accepts the highlighted offer, loading its freight onto the train.
+    pub fn accept(&mut self, train: &mut Train) -> bool {
+        let idx = match self.contracts
+            .iter()
+            .enumerate()
+            .filter(|&(_, c)| c.state == ContractState::Offered)
+            .nth(self.highlighted) {
+            Some((idx, _)) => idx,
+            None => return false,
+        };
+
+        let ok = match self.contracts[idx].freight {
+            Freight::Items(item, n) => {
+                let loaded = cargo::load(train, item, n);
+                if loaded < n {
+                    // not enough room, put it back
+                    cargo::unload(train, item, loaded);
+                    false
+                } else {
+                    true
+                }
+            }
+            Freight::Passengers(n) => {
+                if self.passengers + n <= PASSENGER_CAPACITY {
+                    self.passengers += n;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        if ok {
+            self.contracts[idx].state = ContractState::Active;
+            self.highlighted = 0;
+        } else {
+            self.notify(String::from("NO ROOM FOR THAT CONTRACT"));
+        }
+        ok
+    }
+
+    pub fn spend(&mut self, amount: i32) -> bool {
+        if amount > self.money {
+            return false;
+        }
+        self.money -= amount;
+        true
+    }
+}
+
+pub struct JournalPanel<'a> {
+    background: RectangleShape<'a>,
+    text: Text<'a>,
+    pub visible: bool,
+}
+
+impl<'a> JournalPanel<'a> {
+    pub fn new() -> Self {
+        let mut background = RectangleShape::new().unwrap();
+        background.set_position2f(1000., 20.);
+        background.set_size2f(580., 400.);
+        background.set_fill_color(&Color::new_rgba(0, 0, 0, 170));
+        background.set_outline_color(&Color::white());
+        background.set_outline_thickness(2.);
+
+        JournalPanel {
+            background: background,
+            text: Text::new().unwrap(),
+            visible: true,
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager) {
+        self.text.set_font(fm.get(FontId::Joystix));
+        self.text.set_character_size(16);
+        self.text.set_position(&Vector2f::new(1015., 30.));
+    }
+
+    pub fn update(&mut self, economy: &Economy, stations: &StationManager) {
+        let mut s = format!("MONEY ${}   PASSENGERS {}/{}\n\nJOURNAL\n",
+                            economy.money,
+                            economy.passengers,
+                            PASSENGER_CAPACITY);
+
+        for c in economy.contracts.iter().filter(|c| c.state == ContractState::Active) {
+            s.push_str(&format!("{} TO {} ${} ({}S LEFT)\n",
+                                c.freight.describe(),
+                                stations.stations[c.to].name,
+                                c.payout,
+                                (c.deadline - economy.time).max(0.) as i32));
+        }
+
+        if !economy.notices.is_empty() {
+            s.push('\n');
+            for notice in economy.notices.iter() {
+                s.push_str(notice);
+                s.push('\n');
+            }
+        }
+
+        let offers: Vec<&Contract> = economy.contracts
+            .iter()
+            .filter(|c| c.state == ContractState::Offered)
+            .collect();
+        if !offers.is_empty() {
+            s.push_str("\nOFFERS (TAB: NEXT, ENTER: ACCEPT)\n");
+            for (n, c) in offers.iter().enumerate() {
+                s.push_str(&format!("{} {} TO {} ${}\n",
+                                    if n == economy.highlighted { ">" } else { " " },
+                                    c.freight.describe(),
+                                    stations.stations[c.to].name,
+                                    c.payout));
+            }
+        }
+
+        self.text.set_string(&s);
+    }
+
+    pub fn draw(&self, window: &mut RenderWindow) {
+        if self.visible {
+            window.draw(&self.background);
+            window.draw(&self.text);
+        }
+    }
+}
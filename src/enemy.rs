@@ -7,27 +7,169 @@ use wagon::*;
 use game_consts::*;
 use astar::*;
 use pathfinding::Pathfinding;
+use animation::*;
+use resource_manager::{TextureManager, TextureId};
+use console::{CommandRegistry, CommandContext, arg};
+use train::Train;
+use actor::Actor;
+
+/// How big enemies are drawn, and what they bump into things with.
+const BODY_SIZE: f32 = 25.;
+
+/// Enemies hit crew members this close to them.
+const ATTACK_RANGE: f32 = 40.;
+const ATTACK_COOLDOWN: f32 = 1.;
+
+/// What sort of enemy it is, waves mix them more as the difficulty goes up.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EnemyKind {
+    Bandit,
+    /// Quick on its feet but goes down easily.
+    Runner,
+    /// Slow and takes a beating.
+    Brute,
+}
+
+impl EnemyKind {
+    pub fn all() -> [EnemyKind; 3] {
+        [EnemyKind::Bandit, EnemyKind::Runner, EnemyKind::Brute]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            EnemyKind::Bandit => "BANDIT",
+            EnemyKind::Runner => "RUNNER",
+            EnemyKind::Brute => "BRUTE",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EnemyKind> {
+        EnemyKind::all().iter().cloned().find(|k| k.name() == name.to_uppercase())
+    }
+
+    fn health(&self) -> f32 {
+        match *self {
+            EnemyKind::Bandit => 100.,
+            EnemyKind::Runner => 60.,
+            EnemyKind::Brute => 250.,
+        }
+    }
+
+    fn damage(&self) -> f32 {
+        match *self {
+            EnemyKind::Bandit => 10.,
+            EnemyKind::Runner => 6.,
+            EnemyKind::Brute => 25.,
+        }
+    }
+
+    fn speed_factor(&self) -> f32 {
+        match *self {
+            EnemyKind::Bandit => 1.,
+            EnemyKind::Runner => 1.6,
+            EnemyKind::Brute => 0.7,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match *self {
+            EnemyKind::Bandit => Color::yellow(),
+            EnemyKind::Runner => Color::cyan(),
+            EnemyKind::Brute => Color::new_rgb(255, 90, 60),
+        }
+    }
+}
 
 pub struct Enemy<'a> {
     pub sprite: Sprite<'a>,
+    /// Where the sprite was before the last simulation step, drawing blends from here.
+    pub prev_pos: Vector2f,
+    pub kind: EnemyKind,
     pub inside_wagon: bool,
     pub move_seq: VecDeque<Vector2f>,
+    pub health: f32,
+    /// Seconds until the next attack.
+    pub attack_cooldown: f32,
+    pub animation: Animation<'a>,
 }
 
 impl<'a> Enemy<'a> {
-    pub fn new(texture: &'a Texture) -> Self {
+    pub fn new(tm: &'a TextureManager, kind: EnemyKind) -> Self {
         let mut sprite = Sprite::new().unwrap();
         //sprite.set_size2f(25., 25.);
-        sprite.set_color(&Color::yellow());
+        sprite.set_color(&kind.color());
         sprite.set_position2f(25. + TILE_SIZE_X as f32 * 6.,
                               25. + TILE_SIZE_Y as f32 * 2.);
         sprite.set_origin2f(25. / 2., 25. / 2.);
-        sprite.set_texture(texture, true);
+        sprite.set_texture(tm.get(TextureId::Enemy), true);
 
         Enemy {
+            prev_pos: sprite.get_position(),
             sprite: sprite,
+            kind: kind,
             inside_wagon: false,
             move_seq: VecDeque::new(),
+            health: kind.health(),
+            attack_cooldown: 0.,
+            animation: enemy_animation(tm, BODY_SIZE),
+        }
+    }
+
+    /// A fresh enemy standing at `pos`, inside the train if that's on a floor tile.
+    pub fn new_at(tm: &'a TextureManager, kind: EnemyKind, pos: Vector2f, train: &Train) -> Self {
+        let mut e = Enemy::new(tm, kind);
+        e.sprite.set_position(&pos);
+        e.prev_pos = pos;
+        e.inside_wagon = train.tile_index_at(pos).is_some();
+        e
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0.
+    }
+
+    /// Picks the clip for what the enemy is doing and turns it towards where it's going.
+    pub fn animate(&mut self, dt: f32) {
+        if self.is_dead() {
+            self.animation.play(AnimState::Die);
+        } else if let Some(next) = self.move_seq.front().cloned() {
+            let pos = self.sprite.get_position();
+            self.animation.face(Vector2f::new(next.x - pos.x, next.y - pos.y));
+            self.animation.play(AnimState::Walk);
+        } else {
+            self.animation.play(AnimState::Idle);
+        }
+
+        self.animation.update(dt);
+        self.animation.apply(&mut self.sprite, 0.);
+    }
+}
+
+/// Living enemies hit the closest crew member in reach, once a second.
+pub fn attack_crew(enemies: &mut [Enemy], actors: &mut [Actor], dt: f32) {
+    for e in enemies.iter_mut().filter(|e| !e.is_dead()) {
+        e.attack_cooldown -= dt;
+        if e.attack_cooldown > 0. {
+            continue;
+        }
+
+        let pos = e.sprite.get_position();
+        let mut target = None;
+        let mut best = ATTACK_RANGE;
+        for (idx, a) in actors.iter().enumerate().filter(|&(_, a)| !a.is_dead()) {
+            let to = a.sprite.get_position() - pos;
+            let d = (to.x * to.x + to.y * to.y).sqrt();
+            if d <= best {
+                target = Some(idx);
+                best = d;
+            }
+        }
+
+        if let Some(idx) = target {
+            actors[idx].health -= e.kind.damage();
+            e.attack_cooldown = ATTACK_COOLDOWN;
+            e.animation.face(actors[idx].sprite.get_position() - pos);
+            e.animation.play(AnimState::Attack);
         }
     }
 }
@@ -52,7 +194,8 @@ impl<'a> Pathfinding for Enemy<'a> {
         self.move_seq.pop_front();
     }
     fn get_collision_bounds(&self) -> FloatRect {
-        self.sprite.get_global_bounds()
+        let pos = self.sprite.get_position();
+        FloatRect::new(pos.x - BODY_SIZE / 2., pos.y - BODY_SIZE / 2., BODY_SIZE, BODY_SIZE)
     }
     fn move2f(&mut self, x: f32, y: f32) {
         self.sprite.move2f(x, y);
@@ -60,4 +203,26 @@ impl<'a> Pathfinding for Enemy<'a> {
     fn set_inside_wagon(&mut self, inside: bool) {
         self.inside_wagon = inside;
     }
+    fn get_speed_factor(&self) -> f32 {
+        self.kind.speed_factor()
+    }
+}
+
+pub fn register_commands(registry: &mut CommandRegistry) {
+    registry.register("spawn", "spawn [n] [kind]: spawn enemies at the cursor", cmd_spawn);
+}
+
+fn cmd_spawn(ctx: &mut CommandContext, args: &[&str]) -> Result<String, String> {
+    let n: usize = if args.is_empty() { 1 } else { arg(args, 0, "count")? };
+    let kind = match args.get(1) {
+        Some(name) => EnemyKind::from_name(name).ok_or(format!("unknown enemy kind {}", name))?,
+        None => EnemyKind::Bandit,
+    };
+    for k in 0..n {
+        // fan them out a little so they don't all stand on the same spot
+        let pos = Vector2f::new(ctx.cursor.x + (k % 4) as f32 * 16., ctx.cursor.y + (k / 4) as f32 * 16.);
+        let e = Enemy::new_at(ctx.tm, kind, pos, ctx.train);
+        ctx.enemies.push(e);
+    }
+    Ok(format!("spawned {} enemies", n))
 }
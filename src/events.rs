@@ -0,0 +1,476 @@
+use std::fs::File;
+use std::io::Read;
+
+use rand::Rng;
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use resource_manager::{FontManager, FontId};
+use rng::GameRng;
+use game_consts::*;
+
+/// Distance travelled between two rolls for an event.
+const ROLL_DISTANCE: f32 = 3000.;
+/// Seconds between two rolls for an event when the train isn't going anywhere.
+const ROLL_TIME: f32 = 45.;
+/// Chance that a roll actually brings an event.
+const EVENT_CHANCE: f32 = 0.5;
+
+const POPUP_WIDTH: f32 = 700.;
+const CHOICE_HEIGHT: f32 = 30.;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Trigger {
+    /// Can happen once the train has travelled this far.
+    Distance(f32),
+    /// Can happen once this many seconds have passed.
+    Time(f32),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EventEffect {
+    /// Enemies appear beside the track.
+    Spawn(u32),
+    /// The train stops and can't go on for this many seconds.
+    Stop(f32),
+    Money(i32),
+    /// Coal goes into (or out of) the train's storages.
+    Coal(i32),
+    /// Someone joins the crew.
+    Crew(u32),
+}
+
+#[derive(Clone)]
+pub struct Choice {
+    pub label: String,
+    pub effects: Vec<EventEffect>,
+}
+
+impl Choice {
+    /// True if the player has the `money` it costs and `coal_room` for the coal it brings.
+    pub fn affordable(&self, money: i32, coal_room: u32) -> bool {
+        let mut cost = 0;
+        let mut coal = 0;
+        for effect in self.effects.iter() {
+            match *effect {
+                EventEffect::Money(n) => cost -= n,
+                EventEffect::Coal(n) => coal += n,
+                _ => {}
+            }
+        }
+        cost <= money && coal <= coal_room as i32
+    }
+}
+
+#[derive(Clone)]
+pub struct EventDef {
+    pub name: String,
+    pub text: String,
+    pub weight: u32,
+    pub trigger: Trigger,
+    /// Only ever happens once per game.
+    pub once: bool,
+    /// Applied as soon as the event shows up.
+    pub effects: Vec<EventEffect>,
+    /// If there are any, the player picks one and gets its effects too.
+    pub choices: Vec<Choice>,
+}
+
+/// Reads event definitions. Every `event = name` line starts a new event and the
+/// `key = value` lines after it fill it in, see `res/events/default.events`.
+pub fn load_events(path: &str) -> Result<Vec<EventDef>, String> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut source))
+        .map_err(|e| format!("can't read {}: {}", path, e))?;
+    parse_events(&source).map_err(|e| format!("{}: {}", path, e))
+}
+
+pub fn parse_events(source: &str) -> Result<Vec<EventDef>, String> {
+    let mut events: Vec<EventDef> = vec![];
+    // where each event starts, to point at the ones that can't be closed
+    let mut starts = vec![];
+    for (n, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap().trim();
+        let value = match parts.next() {
+            Some(value) => value.trim(),
+            None => return Err(format!("line {}: expected key = value", n + 1)),
+        };
+
+        if key == "event" {
+            starts.push(n + 1);
+            events.push(EventDef {
+                name: value.to_uppercase(),
+                text: String::new(),
+                weight: 1,
+                trigger: Trigger::Time(0.),
+                once: false,
+                effects: vec![],
+                choices: vec![],
+            });
+            continue;
+        }
+
+        let event = match events.last_mut() {
+            Some(event) => event,
+            None => return Err(format!("line {}: {} before the first event", n + 1, key)),
+        };
+        parse_entry(event, key, value).map_err(|e| format!("line {}: {}", n + 1, e))?;
+    }
+
+    // the game holds still until a choice is picked, so one of them has to be free
+    for (event, line) in events.iter().zip(starts) {
+        if !event.choices.is_empty() && !event.choices.iter().any(|c| c.affordable(0, 0)) {
+            return Err(format!("line {}: every choice of {} costs something", line, event.name));
+        }
+    }
+    Ok(events)
+}
+
+fn parse_entry(event: &mut EventDef, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        // "\n" breaks the line, the pop-up doesn't wrap by itself
+        "text" => event.text = value.replace("\\n", "\n").to_uppercase(),
+        "weight" => event.weight = value.parse().map_err(|_| format!("bad weight {}", value))?,
+        "once" => event.once = value == "true",
+        "trigger" => {
+            let words: Vec<&str> = value.split_whitespace().collect();
+            let amount: f32 = words.get(1)
+                .and_then(|w| w.parse().ok())
+                .ok_or(format!("bad trigger {}", value))?;
+            event.trigger = match words[0] {
+                "distance" => Trigger::Distance(amount),
+                "time" => Trigger::Time(amount),
+                other => return Err(format!("unknown trigger {}", other)),
+            };
+        }
+        "effect" => event.effects.push(parse_effect(value)?),
+        "choice" => {
+            // label | effect | effect...
+            let mut parts = value.split('|').map(|p| p.trim());
+            let label = parts.next().unwrap().to_uppercase();
+            let effects = parts.map(parse_effect).collect::<Result<Vec<_>, _>>()?;
+            event.choices.push(Choice {
+                label: label,
+                effects: effects,
+            });
+        }
+        other => return Err(format!("unknown key {}", other)),
+    }
+    Ok(())
+}
+
+fn parse_effect(s: &str) -> Result<EventEffect, String> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let amount = |n: usize| -> Result<f32, String> {
+        words.get(n).and_then(|w| w.parse().ok()).ok_or(format!("bad effect {}", s))
+    };
+    match words.get(0).cloned() {
+        Some("spawn") => Ok(EventEffect::Spawn(amount(1)? as u32)),
+        Some("stop") => Ok(EventEffect::Stop(amount(1)?)),
+        Some("money") => Ok(EventEffect::Money(amount(1)? as i32)),
+        Some("coal") => Ok(EventEffect::Coal(amount(1)? as i32)),
+        Some("crew") => Ok(EventEffect::Crew(amount(1)? as u32)),
+        _ => Err(format!("unknown effect {}", s)),
+    }
+}
+
+/// Rolls for events as the journey goes on and shows them in a pop-up.
+pub struct EventDirector<'a> {
+    events: Vec<EventDef>,
+    /// Events with `once` that already happened.
+    used: Vec<bool>,
+    last_roll_distance: f32,
+    last_roll_time: f32,
+    /// The event waiting for the player to pick a choice.
+    pub current: Option<usize>,
+    /// The line is blocked and the train can't move for this long.
+    pub blocked_for: f32,
+    background: RectangleShape<'a>,
+    title: Text<'a>,
+    body: Text<'a>,
+    choices: Vec<(FloatRect, Text<'a>)>,
+    font: Option<&'a Font>,
+    /// What the player has to pay with, choices beyond that are greyed out.
+    money: i32,
+    coal_room: u32,
+}
+
+impl<'a> EventDirector<'a> {
+    pub fn new(events: Vec<EventDef>) -> Self {
+        let mut background = RectangleShape::new().unwrap();
+        background.set_fill_color(&Color::new_rgba(20, 20, 20, 235));
+        background.set_outline_color(&Color::yellow());
+        background.set_outline_thickness(2.);
+
+        EventDirector {
+            used: vec![false; events.len()],
+            events: events,
+            last_roll_distance: 0.,
+            last_roll_time: 0.,
+            current: None,
+            blocked_for: 0.,
+            background: background,
+            title: Text::new().unwrap(),
+            body: Text::new().unwrap(),
+            choices: vec![],
+            font: None,
+            money: 0,
+            coal_room: 0,
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager) {
+        let font = fm.get(FontId::Joystix);
+        self.title.set_font(font);
+        self.title.set_character_size(28);
+        self.title.set_color(&Color::yellow());
+        self.body.set_font(font);
+        self.body.set_character_size(16);
+        self.font = Some(font);
+    }
+
+    /// Tells the director what the player can spend, call before it's updated or drawn.
+    pub fn set_means(&mut self, money: i32, coal_room: u32) {
+        if (money, coal_room) != (self.money, self.coal_room) {
+            self.money = money;
+            self.coal_room = coal_room;
+            self.layout();
+        }
+    }
+
+    /// Whether choice `n` of the open event can be picked.
+    fn can_choose(&self, n: usize) -> bool {
+        match self.current.and_then(|idx| self.events[idx].choices.get(n)) {
+            Some(choice) => choice.affordable(self.money, self.coal_room),
+            // the OK of events without choices
+            None => true,
+        }
+    }
+
+    /// True while a pop-up waits for the player, the game holds still meanwhile.
+    pub fn is_open(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Rolls for an event every so often, returns the effects that apply straight away.
+    pub fn update(&mut self, dt: f32, time: f32, distance: f32, rng: &mut GameRng) -> Vec<EventEffect> {
+        if self.blocked_for > 0. {
+            self.blocked_for = (self.blocked_for - dt).max(0.);
+        }
+        if self.current.is_some() {
+            return vec![];
+        }
+
+        let travelled = (distance - self.last_roll_distance).abs();
+        if travelled < ROLL_DISTANCE && time - self.last_roll_time < ROLL_TIME {
+            return vec![];
+        }
+        self.last_roll_distance = distance;
+        self.last_roll_time = time;
+
+        if rng.next_f32() >= EVENT_CHANCE {
+            return vec![];
+        }
+
+        let eligible: Vec<usize> = (0..self.events.len())
+            .filter(|&i| !self.used[i] && self.events[i].weight > 0)
+            .filter(|&i| match self.events[i].trigger {
+                Trigger::Distance(d) => distance.abs() >= d,
+                Trigger::Time(t) => time >= t,
+            })
+            .collect();
+        let total: u32 = eligible.iter().map(|&i| self.events[i].weight).sum();
+        if total == 0 {
+            return vec![];
+        }
+
+        let mut pick = rng.gen_range(0, total);
+        for &i in eligible.iter() {
+            if pick < self.events[i].weight {
+                return self.start(i);
+            }
+            pick -= self.events[i].weight;
+        }
+        vec![]
+    }
+
+    fn start(&mut self, idx: usize) -> Vec<EventEffect> {
+        if self.events[idx].once {
+            self.used[idx] = true;
+        }
+        self.current = Some(idx);
+        self.layout();
+        self.events[idx].effects.clone()
+    }
+
+    fn layout(&mut self) {
+        let (idx, font) = match (self.current, self.font) {
+            (Some(idx), Some(font)) => (idx, font),
+            _ => return,
+        };
+        let event = &self.events[idx];
+
+        let left = (WINDOW_SIZE_X as f32 - POPUP_WIDTH) / 2.;
+        let top = 200.;
+        self.title.set_string(&event.name);
+        self.title.set_position2f(left + 20., top + 15.);
+        self.body.set_string(&event.text);
+        self.body.set_position2f(left + 20., top + 65.);
+
+        // events without choices just get acknowledged
+        let labels: Vec<String> = if event.choices.is_empty() {
+            vec![String::from("OK")]
+        } else {
+            event.choices.iter().map(|c| c.label.clone()).collect()
+        };
+
+        self.choices.clear();
+        let mut y = top + 80. + self.body.get_global_bounds().height + 20.;
+        for (n, label) in labels.iter().enumerate() {
+            let mut text = Text::new().unwrap();
+            text.set_font(font);
+            text.set_character_size(18);
+            text.set_string(&format!("{}. {}", n + 1, label));
+            text.set_position2f(left + 40., y);
+            if !self.can_choose(n) {
+                text.set_color(&Color::new_rgb(110, 110, 110));
+            }
+            let bounds = FloatRect::new(left, y - 4., POPUP_WIDTH, CHOICE_HEIGHT);
+            self.choices.push((bounds, text));
+            y += CHOICE_HEIGHT;
+        }
+
+        self.background.set_position2f(left, top);
+        self.background.set_size2f(POPUP_WIDTH, y - top + 20.);
+    }
+
+    /// The choice under `ui_pos`, if the pop-up is open.
+    pub fn choice_at(&self, ui_pos: Vector2f) -> Option<usize> {
+        if self.current.is_none() {
+            return None;
+        }
+        self.choices.iter().position(|&(bounds, _)| bounds.contains(ui_pos))
+    }
+
+    /// Closes the pop-up with choice `n` and returns its effects.
+    pub fn choose(&mut self, n: usize) -> Vec<EventEffect> {
+        let idx = match self.current {
+            Some(idx) => idx,
+            None => return vec![],
+        };
+        if n >= self.choices.len() || !self.can_choose(n) {
+            return vec![];
+        }
+
+        self.current = None;
+        self.events[idx].choices.get(n).map(|c| c.effects.clone()).unwrap_or(vec![])
+    }
+
+    /// Draws the pop-up, call with the ui view set.
+    pub fn draw(&self, window: &mut RenderWindow) {
+        if self.current.is_none() {
+            return;
+        }
+        window.draw(&self.background);
+        window.draw(&self.title);
+        window.draw(&self.body);
+        for &(_, ref text) in self.choices.iter() {
+            window.draw(text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        parse_events(source).err().unwrap()
+    }
+
+    #[test]
+    fn parses_events_and_choices() {
+        let source = "# comments and blank lines are skipped
+
+event = Toll Bridge
+text = A toll keeper\\nwaves you down.
+weight = 4
+trigger = distance 2000
+once = true
+effect = stop 5
+choice = Pay up | money -20
+choice = Trade coal | coal -10 | money 5
+choice = Drive through | spawn 2
+
+event = quiet stretch
+trigger = time 30
+";
+        let events = parse_events(source).unwrap();
+        assert_eq!(events.len(), 2);
+
+        let toll = &events[0];
+        assert_eq!(toll.name, "TOLL BRIDGE");
+        assert_eq!(toll.text, "A TOLL KEEPER\nWAVES YOU DOWN.");
+        assert_eq!(toll.weight, 4);
+        assert_eq!(toll.trigger, Trigger::Distance(2000.));
+        assert!(toll.once);
+        assert_eq!(toll.effects, vec![EventEffect::Stop(5.)]);
+        let labels: Vec<&str> = toll.choices.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(labels, vec!["PAY UP", "TRADE COAL", "DRIVE THROUGH"]);
+        assert_eq!(toll.choices[0].effects, vec![EventEffect::Money(-20)]);
+        assert_eq!(toll.choices[1].effects, vec![EventEffect::Coal(-10), EventEffect::Money(5)]);
+        assert_eq!(toll.choices[2].effects, vec![EventEffect::Spawn(2)]);
+
+        let quiet = &events[1];
+        assert_eq!(quiet.weight, 1);
+        assert_eq!(quiet.trigger, Trigger::Time(30.));
+        assert!(!quiet.once);
+        assert!(quiet.effects.is_empty() && quiet.choices.is_empty());
+    }
+
+    #[test]
+    fn shipped_events_parse() {
+        assert!(!parse_events(include_str!("../res/events/default.events")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn bad_lines_name_their_line() {
+        assert_eq!(error("weight = 2"), "line 1: weight before the first event");
+        assert_eq!(error("event = a\nweight 2"), "line 2: expected key = value");
+        assert_eq!(error("event = a\ncolour = red"), "line 2: unknown key colour");
+        assert_eq!(error("event = a\nweight = lots"), "line 2: bad weight lots");
+        assert_eq!(error("event = a\ntrigger = distance"), "line 2: bad trigger distance");
+        assert_eq!(error("event = a\ntrigger = weekly 3"), "line 2: unknown trigger weekly");
+        assert_eq!(error("event = a\n\neffect = fly 3"), "line 3: unknown effect fly 3");
+        assert_eq!(error("event = a\nchoice = Pay | money lots"), "line 2: bad effect money lots");
+    }
+
+    #[test]
+    fn one_choice_has_to_be_free() {
+        assert_eq!(error("event = a\n\nevent = toll\nchoice = Pay | money -50"),
+                   "line 3: every choice of TOLL costs something");
+        // coal needs room the player may not have
+        assert_eq!(error("event = gift\nchoice = Take it | coal 10"),
+                   "line 1: every choice of GIFT costs something");
+        assert!(parse_events("event = toll\nchoice = Pay | money -50\nchoice = Refuse | spawn 3").is_ok());
+        assert!(parse_events("event = tip\nchoice = Take it | money 10").is_ok());
+    }
+
+    #[test]
+    fn affordable_checks_money_and_room() {
+        let choice = Choice {
+            label: String::from("BUY"),
+            effects: vec![EventEffect::Money(-30), EventEffect::Coal(20)],
+        };
+        assert!(choice.affordable(30, 20));
+        assert!(!choice.affordable(29, 20));
+        assert!(!choice.affordable(30, 19));
+    }
+}
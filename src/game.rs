@@ -14,7 +14,7 @@ use lyon_bezier::*;
 use state_stack::*;
 use resource_manager::*;
 use particle_manager::*;
-use actor::{Actor};
+use actor::{Actor, ActorKind, Order, crew_name};
 use menu::*;
 use wagon::*;
 use game_consts::*;
@@ -24,9 +24,132 @@ use enemy::*;
 use pathfinding::*;
 use train::*;
 use actor_manager::*;
+use cargo::*;
+use station::*;
+use economy::*;
+use avoidance::*;
+use inspector::*;
+use hud::*;
+use minimap::*;
+use console::*;
+use scripting::*;
+use scenario::*;
+use events::*;
+use waves::*;
+use rng::*;
+use replay::*;
+use timestep::*;
+#[cfg(debug_assertions)]
+use debug::*;
 use std::sync::mpsc::*;
 
 
+/// Opens the game window, either at the default size or fullscreen at the desktop resolution.
+fn open_window(fullscreen: bool) -> RenderWindow {
+    let mut window = if fullscreen {
+        RenderWindow::new(VideoMode::get_desktop_mode(),
+                          "Train",
+                          window_style::FULLSCREEN,
+                          &ContextSettings::default())
+            .unwrap()
+    } else {
+        RenderWindow::new(VideoMode::new_init(WINDOW_SIZE_X, WINDOW_SIZE_Y, 32),
+                          "Train",
+                          window_style::CLOSE | window_style::RESIZE,
+                          &ContextSettings::default())
+            .unwrap()
+    };
+
+    window.set_framerate_limit(120);
+    window.set_vertical_sync_enabled(true);
+    window
+}
+
+/// What the command line asked for: `[scenario] [--seed N] [--record FILE] [--replay FILE]`.
+struct Options {
+    scenario: String,
+    seed: Option<u32>,
+    record: Option<String>,
+    replay: Option<String>,
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        scenario: String::from(DEFAULT_SCENARIO),
+        seed: None,
+        record: None,
+        replay: None,
+    };
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => options.seed = args.next().and_then(|s| s.parse().ok()),
+            "--record" => options.record = args.next(),
+            "--replay" => options.replay = args.next(),
+            _ => options.scenario = arg,
+        }
+    }
+    options
+}
+
+fn register_commands(registry: &mut CommandRegistry) {
+    registry.register("timescale", "timescale <x>: run game time x times as fast", cmd_time_scale);
+    registry.register("dump", "print the game state to stdout", cmd_dump);
+}
+
+fn cmd_time_scale(ctx: &mut CommandContext, args: &[&str]) -> Result<String, String> {
+    let scale: f32 = arg(args, 0, "scale")?;
+    if scale < 0. {
+        return Err(String::from("time can't run backwards"));
+    }
+    *ctx.time_scale = scale;
+    Ok(format!("time scale set to {}", scale))
+}
+
+fn cmd_dump(ctx: &mut CommandContext, _: &[&str]) -> Result<String, String> {
+    let train = &*ctx.train;
+    println!("train: speed {} / {}, moving {}, reversing {}, distance {}",
+             train.current_speed,
+             train.top_speed,
+             train.moving,
+             train.reversing,
+             train.distance);
+    println!("boiler: firebox {}, pressure {}", train.boiler.firebox, train.boiler.pressure);
+    for (idx, a) in ctx.am.actors.iter().enumerate() {
+        let pos = a.sprite.get_position();
+        println!("actor {}: ({}, {}) inside {} path {} health {} selected {}",
+                 idx,
+                 pos.x,
+                 pos.y,
+                 a.inside_wagon,
+                 a.move_seq.len(),
+                 a.health,
+                 ctx.am.selected.contains(&idx));
+    }
+    for (idx, e) in ctx.enemies.iter().enumerate() {
+        let pos = e.sprite.get_position();
+        println!("enemy {}: ({}, {}) inside {} health {}", idx, pos.x, pos.y, e.inside_wagon, e.health);
+    }
+    println!("time scale {}", ctx.time_scale);
+    Ok(String::from("state dumped to stdout"))
+}
+
+/// Maps the number row to control group indices.
+fn digit_of(code: Key) -> Option<usize> {
+    match code {
+        Key::Num1 => Some(0),
+        Key::Num2 => Some(1),
+        Key::Num3 => Some(2),
+        Key::Num4 => Some(3),
+        Key::Num5 => Some(4),
+        Key::Num6 => Some(5),
+        Key::Num7 => Some(6),
+        Key::Num8 => Some(7),
+        Key::Num9 => Some(8),
+        _ => None,
+    }
+}
+
 pub struct EnemyManager<'a> {
     enemies: Vec<Enemy<'a>>,
     channel: (Sender<usize>, Receiver<usize>),
@@ -37,9 +160,14 @@ pub struct Game<'a> {
     music_manager: &'a mut MusicManager,
     window: RenderWindow,
     state_stack: StateStack,
-    pm: ParticleManager<'a>,
-    clock: Clock, // TODO:
-    pf_clock: Clock, // TODO:
+    pm: ParticleManager,
+    clock: Clock,
+    rng: GameRng,
+    replay: ReplayMode,
+    /// Game time not simulated yet, always less than a step after `update`.
+    accumulator: f32,
+    /// What the keyboard and mouse were doing this tick.
+    input: InputState,
     train: Train<'a>,
 
     enemies: Vec<Enemy<'a>>,
@@ -47,11 +175,34 @@ pub struct Game<'a> {
     menu: Menu<'a>,
     world: World<'a>,
     camera: Camera,
-    tile_selection: RectangleShape<'a>,
+    inspector: TileInspector<'a>,
+    hud: Hud<'a>,
+    minimap: Minimap<'a>,
+    console: Console<'a>,
+    scripting: Scripting<'a>,
+    /// Scenario file we're playing, restarting loads it again.
+    scenario_path: String,
+    mission: Option<Mission<'a>>,
+    results: ResultsScreen<'a>,
+    events: EventDirector<'a>,
+    waves: WaveSpawner,
+    /// Game time runs this many times faster than real time.
+    time_scale: f32,
+    #[cfg(debug_assertions)]
+    debug: DebugOverlays<'a>,
+    /// Last known cursor position in window pixels.
+    mouse_pixel: Vector2i,
+    fullscreen: bool,
     paused_text: Text<'a>,
     is_paused: bool,
 
     am: ActorManager<'a>,
+    manifest: ManifestPanel<'a>,
+    stations: StationManager<'a>,
+    status_text: Text<'a>,
+    economy: Economy,
+    journal: JournalPanel<'a>,
+    avoidance: Avoidance,
 }
 
 impl<'a> Game<'a> {
@@ -62,21 +213,11 @@ impl<'a> Game<'a> {
 
     pub fn new(resources: &'a Resources, music_manager: &'a mut MusicManager) -> Self {
         // Create the window of the application
-        let mut window = RenderWindow::new(VideoMode::new_init(WINDOW_SIZE_X, WINDOW_SIZE_Y, 32),
-                                           "Train",
-                                           window_style::CLOSE,
-                                           &ContextSettings::default()).unwrap();
-
-        window.set_framerate_limit(120);
-        window.set_vertical_sync_enabled(true);
+        let window = open_window(false);
 
         let mut state_stack = StateStack::new();
         state_stack.push(StateType::Playing);
 
-        let mut tile_selection = RectangleShape::new().unwrap();
-        tile_selection.set_size2f(TILE_SIZE_X as f32, TILE_SIZE_Y as f32);
-        tile_selection.set_fill_color(&Color::new_rgba(255, 255, 0, 60));
-
         let mut selection_rect = RectangleShape::new().unwrap();
         selection_rect.set_size2f(0., 0.);
         selection_rect.set_fill_color(&Color::new_rgba(0, 255, 0, 150));
@@ -88,126 +229,402 @@ impl<'a> Game<'a> {
             state_stack: state_stack,
             pm: ParticleManager::new(),
             clock: Clock::new(),
-            pf_clock: Clock::new(),
+            rng: GameRng::new(0),
+            replay: ReplayMode::Off,
+            accumulator: 0.,
+            input: InputState::new(),
             train: Train::new(),
             enemies: vec![],
             menu: Menu { buttons: vec![] },
-            world: World {
-                bgs: vec![],
-                rails: vec![],
-                connectors: vec![],
-                curves: vec![],
-            },
+            world: World::new(),
             camera: Camera::new(),
-            tile_selection: tile_selection,
+            inspector: TileInspector::new(),
+            hud: Hud::new(),
+            minimap: Minimap::new(),
+            console: Console::new(),
+            scripting: Scripting::new(),
+            scenario_path: String::from(DEFAULT_SCENARIO),
+            mission: None,
+            results: ResultsScreen::new(),
+            events: EventDirector::new(vec![]),
+            waves: WaveSpawner::new(None),
+            time_scale: 1.,
+            #[cfg(debug_assertions)]
+            debug: DebugOverlays::new(),
+            mouse_pixel: Vector2i::new(0, 0),
+            fullscreen: false,
             paused_text: Text::new().unwrap(),
             is_paused: false,
             am: ActorManager::new(),
+            manifest: ManifestPanel::new(),
+            stations: StationManager::new(),
+            status_text: Text::new().unwrap(),
+            economy: Economy::new(100),
+            journal: JournalPanel::new(),
+            avoidance: Avoidance::new(),
         }
     }
 
     pub fn run(&mut self) -> Result<(), &'static str> {
-        self.init();
+        if let Err(e) = self.init() {
+            println!("{}", e);
+            return Err("couldn't start the scenario");
+        }
 
         while self.window.is_open() {
-            self.process_events();
-            self.update();
+            let frame = self.next_frame();
+            self.input = frame.input;
+            self.process_events(frame.actions);
+            self.update(frame.dt);
             self.render();
         }
         Ok(())
     }
 
-    /// Initializes all the game objects (Example: run this to start a new game)
-    fn init(&mut self) {
-        self.camera.game = self.window.get_default_view();
-
-        self.world.init(&self.resources.tm);
+    /// Sets up everything that lives across restarts, then starts the scenario.
+    fn init(&mut self) -> Result<(), String> {
+        let options = parse_args();
+        self.scenario_path = options.scenario;
+        let mut seed = options.seed.unwrap_or_else(GameRng::random_seed);
+        self.replay = if let Some(path) = options.replay {
+            // a replay brings its own seed and scenario
+            let playback = Playback::load(&path)?;
+            seed = playback.seed;
+            self.scenario_path = playback.scenario.clone();
+            println!("playing back {}", path);
+            ReplayMode::Playing(playback)
+        } else if let Some(path) = options.record {
+            println!("recording to {}", path);
+            ReplayMode::Recording(Recorder::create(&path, seed, &self.scenario_path)?)
+        } else {
+            ReplayMode::Off
+        };
+        println!("seed {}", seed);
+        self.rng = GameRng::new(seed);
+
+        self.camera.init(self.window.get_default_view());
+        let size = self.window.get_size();
+        self.camera.resize(size.x, size.y);
 
         self.menu.init(&self.resources.fm);
 
+        self.manifest.init(&self.resources.fm);
+        self.journal.init(&self.resources.fm);
+        self.inspector.init(&self.resources.fm);
+        self.hud.init(&self.resources.fm, &self.resources.tm);
+        self.console.init(&self.resources.fm);
+        self.results.init(&self.resources.fm);
 
-        //--------
+        register_commands(&mut self.console.registry);
+        ::train::register_commands(&mut self.console.registry);
+        ::enemy::register_commands(&mut self.console.registry);
+        ::actor_manager::register_commands(&mut self.console.registry);
+        #[cfg(debug_assertions)]
+        self.debug.init(&self.resources.fm);
 
-        self.train.init(1000., 30.); // top speed, accel
-        self.train.wagons.push(Wagon::new(&self.resources.tm, 2, 9));
+        self.status_text.set_font(&self.resources.fm.get(FontId::Joystix));
+        self.status_text.set_character_size(24);
+        self.status_text.set_position2f(WINDOW_SIZE_X as f32 / 2. - 300., 20.);
 
-        let mut y_size: i32 = 5;
-        for x in 0..2 {
-            let mut new_wag = Wagon::new(&self.resources.tm, 9, 3);
-            y_size += if x % 2 == 0 { 2 } else { -2 };
+        self.paused_text.set_font(&self.resources.fm.get(FontId::Joystix));
+        self.paused_text.set_string("PAUSED");
+        self.paused_text.set_character_size(36);
+        self.paused_text.set_position2f(WINDOW_SIZE_X as f32 / 2. - 100., WINDOW_SIZE_Y as f32 / 2. + 150.);
 
-            self.train.wagons.last_mut().unwrap().connect(&mut new_wag, &self.resources.tm);
+        self.start_scenario()
+    }
 
-            self.train.wagons.push(new_wag);
+    /// Throws away the current game and sets up the scenario from scratch (run this to start a new game).
+    fn start_scenario(&mut self) -> Result<(), String> {
+        let resources = self.resources;
+        let scenario = Scenario::load(&self.scenario_path)?;
+
+        self.world = World::new();
+        self.world.init(&resources.tm);
+
+        self.train = Train::new();
+        scenario.build_train(&mut self.train, &resources.tm)?;
+
+        self.am = ActorManager::new();
+        self.am.init_font(&resources.fm);
+        scenario.spawn_crew(&mut self.am, &self.train, &resources.tm)?;
+        self.am.wait_for_paths = match self.replay {
+            ReplayMode::Off => false,
+            _ => true,
+        };
+
+        self.enemies = vec![];
+        self.avoidance = Avoidance::new();
+        self.stations = StationManager::new();
+        self.stations.init(&resources.fm);
+        self.economy = Economy::new(scenario.money);
+
+        self.scripting = Scripting::new();
+        self.scripting.init(&resources.fm);
+        if let Some(ref script) = scenario.script {
+            self.scripting.load(script);
         }
 
-        self.train.set_position2f(0., 0.);
-        self.train.rebuild_pfgrids();
-
-        // 'wagons: for wagon in self.train.wagons.iter_mut() {
-        //     let global_middle = wagon.get_origin() + wagon.get_middle();
-        //     let origin = wagon.get_origin();
-
-        //     for rail in self.world.rails.iter() {
-        //         if global_middle.x > rail.get_position().x &&
-        //             global_middle.x < rail.get_size().x + rail.get_position().x {
-        //                 wagon.set_position2f(origin.x, rail.get_position().y - TILE_SIZE_Y as f32);
-        //                 wagon.rotate(rail.get_rotation());
+        let events = match scenario.events {
+            Some(ref path) => load_events(path)?,
+            None => vec![],
+        };
+        self.events = EventDirector::new(events);
+        self.events.init(&resources.fm);
+        self.waves = WaveSpawner::new(scenario.waves.clone());
+
+        self.time_scale = 1.;
+        self.accumulator = 0.;
+        self.is_paused = false;
+        self.camera.mode = CameraMode::FollowTrain;
+
+        let mut mission = Mission::new(scenario);
+        mission.init(&resources.fm);
+        self.mission = Some(mission);
+        Ok(())
+    }
 
-        //                 continue 'wagons;
-        //         }
-        //     }
-        // }
+    /// Switches between windowed and fullscreen, the window has to be opened again for that.
+    fn toggle_fullscreen(&mut self) {
+        self.fullscreen = !self.fullscreen;
+        self.window = open_window(self.fullscreen);
 
+        let size = self.window.get_size();
+        self.camera.resize(size.x, size.y);
+    }
 
-        //---------
+    /// Carries out what the scripts asked for, ignoring anything that doesn't make sense.
+    fn apply_script_actions(&mut self) {
+        for action in self.scripting.take_actions() {
+            match action {
+                ScriptAction::SpawnEnemy(pos) => {
+                    self.enemies.push(Enemy::new_at(&self.resources.tm, EnemyKind::Bandit, pos, &self.train));
+                }
+                ScriptAction::OpenDoor(w, i, j) => {
+                    let closed_door = match self.train.wagons.get(w).and_then(|wagon| wagon.tiles.get(i)).and_then(|row| row.get(j)) {
+                        Some(tile) => tile.is_solid && if let TileType::Door(_) = tile.tile_type { true } else { false },
+                        None => false,
+                    };
+                    let platform = self.stations.docked_station().map(|s| &s.side);
+                    if closed_door && self.train.toggle_door((w, i, j), platform, &self.resources.tm) {
+                        self.scripting.on_door_opened((w, i, j), &self.train, &self.enemies);
+                    } else {
+                        println!("script can't open door {} {} {}", w, i, j);
+                    }
+                }
+                // the scripting side shows those itself
+                ScriptAction::Message(_) => {}
+            }
+        }
+    }
 
-        self.am.init_actors(&self.resources.tm);
+    fn apply_event_effects(&mut self, effects: Vec<EventEffect>) {
+        for effect in effects {
+            match effect {
+                EventEffect::Spawn(n) => {
+                    // beside the track just ahead of the locomotive, on both sides
+                    let front = self.train.front_x();
+                    for k in 0..n {
+                        let x = front + 200. + (k / 2) as f32 * TILE_SIZE_X as f32;
+                        let track_y = self.world.track_y_at(x).unwrap_or(self.train.get_origin().y);
+                        let side = if k % 2 == 0 { -1. } else { 1. };
+                        let pos = Vector2f::new(x, track_y + side * 4. * TILE_SIZE_Y as f32);
+                        self.enemies.push(Enemy::new_at(&self.resources.tm, EnemyKind::Bandit, pos, &self.train));
+                    }
+                }
+                EventEffect::Stop(seconds) => {
+                    self.train.moving = false;
+                    self.events.blocked_for = self.events.blocked_for.max(seconds);
+                }
+                EventEffect::Money(n) => self.economy.money = (self.economy.money + n).max(0),
+                EventEffect::Coal(n) if n >= 0 => {
+                    let loaded = load(&mut self.train, ItemType::Coal, n as u32);
+                    if loaded < n as u32 {
+                        self.economy.notify(format!("NO ROOM FOR {} COAL", n as u32 - loaded));
+                    }
+                }
+                EventEffect::Coal(n) => {
+                    unload(&mut self.train, ItemType::Coal, (-n) as u32);
+                }
+                EventEffect::Crew(n) => {
+                    let mut left_out = 0;
+                    for _ in 0..n {
+                        match self.free_floor_tile() {
+                            Some(pos) => self.am.spawn_actor(&self.resources.tm, ActorKind::Crew, pos),
+                            None => left_out += 1,
+                        }
+                    }
+                    if left_out > 0 {
+                        self.economy.notify(format!("NO ROOM FOR {} CREW", left_out));
+                    }
+                }
+            }
+        }
+    }
 
-        self.enemies = vec![Enemy::new(&self.resources.tm.get(TextureId::Enemy)),
-                            Enemy::new(&self.resources.tm.get(TextureId::Enemy))];
+    /// Center of a bare floor tile nobody is standing on.
+    fn free_floor_tile(&self) -> Option<Vector2f> {
+        for w in self.train.wagons.iter() {
+            for t in w.tiles.iter().flat_map(|row| row.iter()) {
+                if t.is_solid || t.tile_type != TileType::WallAndFloor || t.storage.is_some() {
+                    continue;
+                }
+                if !self.am.actors.iter().any(|a| t.contains(a.sprite.get_position())) {
+                    return Some(t.sprite.get_position());
+                }
+            }
+        }
+        None
+    }
 
-        for (x, e) in self.enemies.iter_mut().enumerate() {
-            e.sprite.move2f((x as u32 * TILE_SIZE_X) as f32, 0.);
+    /// Input and frame time for the next tick, from the player or from the replay being played back.
+    fn next_frame(&mut self) -> Frame {
+        let actions: Vec<InputAction> = self.window.events().filter_map(|e| InputAction::from_event(&e)).collect();
+        let dt = self.clock.restart().as_seconds();
+
+        let mut finished = false;
+        if let ReplayMode::Playing(ref mut playback) = self.replay {
+            match playback.next_frame() {
+                Some(frame) => {
+                    // the replay gets all the input, but closing the window still works
+                    if actions.contains(&InputAction::Closed) {
+                        self.window.close();
+                    }
+                    return frame;
+                }
+                None => finished = true,
+            }
+        }
+        if finished {
+            println!("replay finished, over to you");
+            self.replay = ReplayMode::Off;
         }
 
-        self.paused_text.set_font(&self.resources.fm.get(FontId::Joystix));
-        self.paused_text.set_string("PAUSED");
-        self.paused_text.set_character_size(36);
-        self.paused_text.set_position2f(WINDOW_SIZE_X as f32 / 2. - 100., WINDOW_SIZE_Y as f32 / 2. + 300.);
+        let frame = Frame {
+            dt: dt,
+            input: InputState::poll(&self.window),
+            actions: actions,
+        };
+        if let ReplayMode::Recording(ref mut recorder) = self.replay {
+            recorder.record(&frame);
+        }
+        frame
     }
 
-    fn process_events(&mut self) {
-        for event in self.window.events() {
+    fn process_events(&mut self, actions: Vec<InputAction>) {
+        let mut toggle_fullscreen = false;
+        let mut restart = false;
+        for event in actions.iter().map(|a| a.to_event()) {
+            // these work the same whatever state we're in
+            match event {
+                event::Resized { width, height } => self.camera.resize(width, height),
+                event::KeyReleased { code: Key::F11, .. } => toggle_fullscreen = true,
+                _ => {}
+            }
+
             match *self.state_stack.top().unwrap() {
                 StateType::Playing => {
                     // Camera movement
-                    if MouseButton::Middle.is_pressed() {
+                    if self.input.middle_mouse {
                         self.camera.move_by_mouse(&self.window
-                            .map_pixel_to_coords_current_view(&self.window.get_mouse_position()));
+                            .map_pixel_to_coords_current_view(&self.input.mouse));
 
                         self.window.set_view(&self.camera.game);
                     }
                     self.camera.mouse_pos_old = self.window
-                        .map_pixel_to_coords_current_view(&self.window.get_mouse_position());
+                        .map_pixel_to_coords_current_view(&self.input.mouse);
+
+                    // an event waits for a choice, by number key or by clicking it
+                    if self.events.is_open() {
+                        let choice = match event {
+                            event::Closed => {
+                                self.window.close();
+                                None
+                            }
+                            event::KeyReleased { code, .. } => digit_of(code),
+                            event::MouseButtonPressed { button: MouseButton::Left, .. } => {
+                                let ui_pos = self.window.map_pixel_to_coords(&self.input.mouse, &self.camera.ui);
+                                self.events.choice_at(ui_pos)
+                            }
+                            _ => None,
+                        };
+                        if let Some(n) = choice {
+                            let effects = self.events.choose(n);
+                            self.apply_event_effects(effects);
+                        }
+                        continue;
+                    }
+
+                    // the console eats all input while it's open
+                    if self.console.visible {
+                        match event {
+                            event::Closed => self.window.close(),
+                            event::KeyReleased { code: Key::Tilde, .. } => self.console.toggle(),
+                            event::KeyPressed { code: Key::BackSpace, .. } => self.console.backspace(),
+                            event::KeyPressed { code: Key::Up, .. } => self.console.recall(),
+                            event::KeyPressed { code: Key::Return, .. } => {
+                                let resources = self.resources;
+                                let cursor = self.window.map_pixel_to_coords(&self.mouse_pixel, &self.camera.game);
+                                let mut ctx = CommandContext {
+                                    train: &mut self.train,
+                                    enemies: &mut self.enemies,
+                                    am: &mut self.am,
+                                    stations: &self.stations,
+                                    scripting: &mut self.scripting,
+                                    tm: &resources.tm,
+                                    cursor: cursor,
+                                    time_scale: &mut self.time_scale,
+                                };
+                                self.console.submit(&mut ctx);
+                            }
+                            event::TextEntered { code } => self.console.type_char(code),
+                            event::MouseMoved { x, y, .. } => self.mouse_pixel = Vector2i::new(x, y),
+                            _ => {}
+                        }
+                        continue;
+                    }
 
                     match event {
                         event::Closed => self.window.close(),
                         event::MouseMoved { x, y, .. } => {
-                            // move the tile selection rectangle
+                            self.mouse_pixel = Vector2i::new(x, y);
                             let coords = self.window
                                 .map_pixel_to_coords_current_view(&Vector2i::new(x, y));
-                            self.tile_selection.set_position2f(((TILE_SIZE_X as u32) * (coords.x as u32 / TILE_SIZE_X)) as f32,
-                                                               ((TILE_SIZE_Y as u32) * (coords.y as u32 / TILE_SIZE_Y)) as f32);
 
                             // update actor selection rectangle
-                            self.am.update_selection_rect(x, y);
+                            self.am.update_selection_rect(&coords);
                         }
                         event::MouseButtonPressed { button, .. } => {
                             match button {
                                 MouseButton::Left => {
+                                    // the minimap moves the camera, a portrait selects that actor and looks at it
+                                    let ui_pos = self.window.map_pixel_to_coords(&self.input.mouse, &self.camera.ui);
+                                    if let Some(pos) = self.minimap.world_at(ui_pos) {
+                                        self.camera.center_on(&pos);
+                                        continue;
+                                    }
+                                    if let Some(idx) = self.hud.portrait_at(ui_pos).and_then(|id| self.am.index_of(id)) {
+                                        self.am.select(vec![idx]);
+                                        let pos = self.am.actors[idx].sprite.get_position();
+                                        self.camera.center_on(&pos);
+                                        continue;
+                                    }
+
+                                    let coords = self.get_coords_of(&self.input.mouse);
+
+                                    // clicking a door opens or closes it instead of selecting
+                                    if let Some((w, i, j)) = self.train.tile_index_at_any(coords) {
+                                        if let TileType::Door(_) = self.train.wagons[w].tiles[i][j].tile_type {
+                                            let platform = self.stations.docked_station().map(|s| &s.side);
+                                            if self.train.toggle_door((w, i, j), platform, &self.resources.tm) &&
+                                               !self.train.wagons[w].tiles[i][j].is_solid {
+                                                self.scripting.on_door_opened((w, i, j), &self.train, &self.enemies);
+                                            }
+                                            continue;
+                                        }
+                                    }
+
                                     // start actor selection
-                                    let coords = self.get_coords_of(&self.window.get_mouse_position());
                                     self.am.start_selection(&coords);
 
                                     // if !self.am.selected.is_empty() {
@@ -275,11 +692,49 @@ impl<'a> Game<'a> {
                                 }
                                 MouseButton::Right => {
                                     // launch movement orders to separate threads for all selected actors
-                                    let click_pos = self.get_coords_of(&self.window.get_mouse_position());
+                                    let click_pos = self.get_coords_of(&self.input.mouse);
+
+                                    // standing orders and queued waypoints
+                                    if self.input.shift {
+                                        self.am.queue_waypoint(click_pos);
+                                        continue;
+                                    }
+                                    if self.input.p {
+                                        self.am.add_patrol_point(click_pos);
+                                        continue;
+                                    }
+                                    if self.input.f {
+                                        if let Some(target) = self.am.actor_at(click_pos) {
+                                            let id = self.am.actors[target].id;
+                                            self.am.give_order(Order::Follow(id));
+                                        }
+                                        continue;
+                                    }
+                                    if self.input.t {
+                                        self.am.give_order(Order::Guard(click_pos));
+                                        continue;
+                                    }
+                                    self.am.clear_orders();
+
+                                    // right clicking a storage tile means go haul cargo there
+                                    let task = match self.train.tile_index_at(click_pos) {
+                                        Some((w, i, j)) if self.train.wagons[w].tiles[i][j].storage.is_some() => {
+                                            Some(Task::Haul((w, i, j)))
+                                        }
+                                        Some((w, i, j)) if self.train.wagons[w].tiles[i][j].tile_type == TileType::Firebox => {
+                                            Some(Task::Stoke((w, i, j)))
+                                        }
+                                        _ => None,
+                                    };
+                                    // everyone goes to the task tile, otherwise spread out
+                                    let spread = task.is_none();
+                                    self.am.set_task(task);
+
                                     self.am.launch_movement_orders(self.train.pfgrid_in.clone(),
                                                                    self.train.pfgrid_out.clone(),
                                                                    self.train.get_origin(),
-                                                                   click_pos);
+                                                                   click_pos,
+                                                                   spread);
 
                                 }
                                 _ => {}
@@ -290,7 +745,14 @@ impl<'a> Game<'a> {
                                 MouseButton::Left => {
                                     // MBleft released => we actually select the actors
                                     // inside the selection rectangle
-                                    self.am.apply_selection();
+                                    let mode = if self.input.ctrl {
+                                        SelectMode::Toggle
+                                    } else if self.input.shift {
+                                        SelectMode::Add
+                                    } else {
+                                        SelectMode::Replace
+                                    };
+                                    self.am.apply_selection(mode, &self.camera.visible_rect());
                                 }
                                 _ => {}
                             }
@@ -300,7 +762,21 @@ impl<'a> Game<'a> {
                             self.window.set_view(&self.camera.game);
 //                            self.world.recalculate_drawables(&self.camera.view, &self.window.map_pixel_to_coords_current_view(&Vector2i::new(0, 0)), &self.resources.tm);
                         }
-                        event::KeyReleased { code, .. } => {
+                        event::KeyReleased { code, ctrl, .. } => {
+                            if let Key::Tilde = code {
+                                self.console.toggle();
+                            }
+
+                            // control groups
+                            if let Some(n) = digit_of(code) {
+                                if ctrl {
+                                    self.am.assign_group(n);
+                                } else if let Some(center) = self.am.recall_group(n) {
+                                    self.camera.center_on(&center);
+                                    self.window.set_view(&self.camera.game);
+                                }
+                            }
+
                             if let Key::Escape = code {
                                 self.state_stack.push(StateType::Menu);
                                 println!("{:?}", self.state_stack);
@@ -320,8 +796,59 @@ impl<'a> Game<'a> {
                                 }
                             }
 
+                            if let Key::M = code {
+                                self.manifest.visible = !self.manifest.visible;
+                            }
+
+                            if let Key::H = code {
+                                self.am.haul_item = self.am.haul_item.next();
+                            }
+
+                            if let Key::R = code {
+                                self.train.toggle_reverse();
+                            }
+
+                            if let Key::L = code {
+                                // take on coal from the station we're docked at
+                                if let Some(station) = self.stations.docked_station_mut() {
+                                    let affordable = (self.economy.money / COAL_PRICE).max(0) as u32;
+                                    let loaded = load(&mut self.train, ItemType::Coal, station.coal.min(affordable));
+                                    station.coal -= loaded;
+                                    self.economy.spend(loaded as i32 * COAL_PRICE);
+                                }
+                            }
+
+                            #[cfg(debug_assertions)]
+                            self.debug.toggle(code);
+
+                            if let Key::C = code {
+                                let unit = self.am.selected.first().map(|&idx| self.am.actors[idx].id);
+                                self.camera.cycle_mode(unit);
+                            }
+
+                            if let Key::V = code {
+                                self.camera.rotate_with_train = !self.camera.rotate_with_train;
+                            }
+
+                            if let Key::N = code {
+                                self.minimap.visible = !self.minimap.visible;
+                            }
+
+                            if let Key::J = code {
+                                self.journal.visible = !self.journal.visible;
+                            }
+
+                            if let Key::Tab = code {
+                                self.economy.highlight_next();
+                            }
+
+                            if let Key::Return = code {
+                                self.economy.accept(&mut self.train);
+                            }
+
                             if let Key::G = code {
-                                self.train.moving = !self.train.moving;
+                                // nothing goes anywhere while the line is blocked
+                                self.train.moving = !self.train.moving && self.events.blocked_for <= 0.;
                                 {
                                     let mut train_sound = self.music_manager.get_mut(MusicId::Train);
                                     train_sound.set_loop(true);
@@ -355,8 +882,8 @@ impl<'a> Game<'a> {
                             }
                         }
                         event::MouseMoved { x, y, .. } => {
+                            let mouse_pos = self.window.map_pixel_to_coords(&Vector2i::new(x, y), &self.camera.ui);
                             for button in &mut self.menu.buttons {
-                                let mouse_pos = Vector2f::new(x as f32, y as f32);
 
                                 if button.text.get_global_bounds().contains(mouse_pos) {
                                     button.text.set_color(&Color::green());
@@ -390,184 +917,310 @@ impl<'a> Game<'a> {
                     }
                 }
                 StateType::GameOver => {
-                    //     match event {
-                    //         event::Closed => {
-                    //             window.close();
-                    //         }
-                    //         event::KeyReleased { code, .. } => {
-                    //             match code {
-                    //                 Key::R => {
-                    //                     // reset the game
-                    //                     state_stack.pop();
-
-                    //                 }
-                    //                 _ => {}
-                    //             }
-                    //         }
-                    //         _ => {}
-                    //     }
+                    match event {
+                        event::Closed => self.window.close(),
+                        event::KeyReleased { code: Key::R, .. } => restart = true,
+                        event::KeyReleased { code: Key::Escape, .. } => self.window.close(),
+                        _ => {}
+                    }
                 }
             }
         }
+
+        if toggle_fullscreen {
+            self.toggle_fullscreen();
+        }
+
+        if restart {
+            self.state_stack.pop();
+            if let Err(e) = self.start_scenario() {
+                println!("{}", e);
+                self.window.close();
+            }
+        }
     }
 
-    fn update(&mut self) {
-        let time = self.clock.restart();
+    /// `frame_dt` is the real time since the last tick, before `time_scale`.
+    fn update(&mut self, frame_dt: f32) {
         match *self.state_stack.top().unwrap() {
             StateType::Playing => {
                 let train_origin = self.train.get_origin();
 
 
-                self.am.update_threads(train_origin);
+                self.am.update_threads(train_origin, frame_dt);
+                self.manifest.update(&self.train, self.am.carried(), self.am.haul_item);
+                self.events.set_means(self.economy.money, free_space(&self.train));
 
+                #[cfg(debug_assertions)]
+                self.debug.update(frame_dt);
 
+                // the camera keeps moving while paused so you can look around
+                {
+                    let follow = match self.camera.mode {
+                        CameraMode::Free => None,
+                        CameraMode::FollowTrain => {
+                            let front = self.train.wagons[0].get_origin();
+                            let rear = self.train.wagons.last().unwrap().get_origin();
+                            Some(Vector2f::new((front.x + rear.x) / 2., (front.y + rear.y) / 2.))
+                        }
+                        CameraMode::FollowUnit(id) => self.am.index_of(id).map(|idx| self.am.actors[idx].sprite.get_position()),
+                    };
+                    let pan = if self.console.visible {
+                        Vector2f::new(0., 0.)
+                    } else {
+                        Camera::pan_input(&self.input, self.window.get_size())
+                    };
+                    self.camera.update(frame_dt, follow, self.train.wagons[1].rotation, pan);
+                }
 
-                if !self.is_paused {
-                    let dt = time.as_seconds();
-
-                    for a in self.am.actors.iter_mut() {
-                        a.update_movement(&self.train.wagons, dt);
+                // an event pop-up holds the game like a pause does
+                if !self.is_paused && !self.events.is_open() {
+                    self.accumulator += frame_dt * self.time_scale;
+                    let mut steps = 0;
+                    while self.accumulator >= SIM_STEP && self.is_simulating() {
+                        self.step(SIM_STEP);
+                        self.accumulator -= SIM_STEP;
+                        steps += 1;
+                        if steps == MAX_STEPS {
+                            // too far behind to catch up, let the game slow down instead of snowballing
+                            self.accumulator = 0.;
+                        }
                     }
+                }
+            }
+            _ => {}
+        }
+    }
 
-                    let time_elapsed = self.pf_clock.get_elapsed_time();
-                    if time_elapsed.as_seconds() >= 1. {
-                        let train_origin = self.train.get_origin();
-
-                        let enemy_destination = self.train.wagons[0].tiles[0][2].sprite.get_position();
+    /// Whether simulation steps should run right now.
+    fn is_simulating(&self) -> bool {
+        let playing = match *self.state_stack.top().unwrap() {
+            StateType::Playing => true,
+            _ => false,
+        };
+        playing && !self.is_paused && !self.events.is_open()
+    }
 
+    /// Advances the game by one fixed step of `dt` game seconds.
+    fn step(&mut self, dt: f32) {
+        let train_origin = self.train.get_origin();
 
-                        // let (tx, rx) = mpsc::channel();
+        // drawing blends from here to wherever this step takes them
+        for a in self.am.actors.iter_mut() {
+            a.prev_pos = a.sprite.get_position();
+        }
+        for e in self.enemies.iter_mut() {
+            e.prev_pos = e.sprite.get_position();
+        }
 
-                        for (idx, e) in self.enemies.iter_mut().enumerate() {
+        // dead enemies stay around until they've finished dying
+        let killed: Vec<Vector2f> = self.enemies
+            .iter()
+            .filter(|e| e.is_dead() && e.animation.is_finished())
+            .map(|e| e.sprite.get_position())
+            .collect();
+        self.enemies.retain(|e| !(e.is_dead() && e.animation.is_finished()));
+        if !killed.is_empty() {
+            // the survivors moved to new indices
+            self.avoidance.forget_enemies();
+        }
+        for pos in killed {
+            self.scripting.on_enemy_killed(pos, &self.train, &self.enemies);
+        }
 
-                            // TODO: WRONG!
-                        //     let enemy_pos = e.sprite.get_position().clone();
+        // fallen crew are gone for good
+        let dead = self.am.remove_dead();
+        self.avoidance.forget_actors(&dead);
+        for id in dead {
+            self.economy.notify(format!("{} DIED", crew_name(id)));
+        }
 
-                        //     let pfgrid = self.train.pfgrid_out.clone();
+        self.scripting.on_tick(dt, &self.train, &self.enemies);
+        self.apply_script_actions();
 
-                        //     let tx = tx.clone();
+        // keep units from walking through each other
+        let steering = {
+            let mut units = vec![];
+            for a in self.am.actors.iter() {
+                units.push((UnitId::Actor(a.id), a.sprite.get_position(), a.move_seq.front().cloned()));
+            }
+            // the dead don't hold on to tiles
+            for (idx, e) in self.enemies.iter().enumerate().filter(|&(_, e)| !e.is_dead()) {
+                units.push((UnitId::Enemy(idx), e.sprite.get_position(), e.move_seq.front().cloned()));
+            }
+            self.avoidance.update(&units, &self.train.pfgrid_all, train_origin, dt)
+        };
+
+        let mut replans = vec![];
+        for (idx, a) in self.am.actors.iter_mut().enumerate() {
+            let s = &steering[idx];
+            a.update_movement_steered(&self.train.wagons, s.steer, s.blocked, dt);
+            if let Some(tile) = s.replan {
+                replans.push((idx, tile));
+            }
 
-                        //     self.handles.push((idx, thread::spawn(move || {
-                        //         let path_result = compute_path(enemy_pos,
-                        //                                        pfgrid,
-                        //                                        train_origin,
-                        //                                        enemy_destination);
-                        //         tx.send(idx).unwrap();
-                        //         path_result
-                        //     })));
-                            e.update_movement(&self.train.wagons, dt);
-                        }
+            let tile_type = self.train.tile_at(a.sprite.get_position()).map(|t| &t.tile_type);
+            a.needs.update(tile_type, dt);
+        }
+        for (idx, tile) in replans {
+            self.am.replan(idx, tile, &self.train.pfgrid_in, &self.train.pfgrid_out, train_origin);
+        }
 
-                        // let train_origin = self.train.get_origin();
-                        // TODO: WRONG!
-                        // let all_threads_finished = true;
-                        // for _ in 0..self.enemies.len() {
-                        //     let idx = rx.recv().unwrap();
-                        //     println!("{} started", idx);
-                        //     self.enemies[idx].set_path(self.handles.swap_remove(0).1.join().unwrap().unwrap(),
-                        //                                train_origin);
-                        //     println!("{} finished", idx);
-                        // }
-
-                        // for &mut (idx, ref handle) in self.handles.iter_mut() {
-                        //     match handle.join() {
-                        //         Ok(Some(path)) => {
-                        //             self.enemies[idx].set_path(path, train_origin);
-                        //         }
-                        //         Ok(None) => {
-                        //             println!("No path available!");
-                        //         }
-                        //         Err(_) => {} // dont care
-                        //     }
-                        // }
+        self.am.update_tasks(&mut self.train, dt);
+        self.am.update_orders(&self.train.pfgrid_in,
+                              &self.train.pfgrid_out,
+                              train_origin,
+                              &mut self.enemies,
+                              dt);
+
+        // enemies give the crew a second to get going
+        if self.economy.time >= 1. {
+            attack_crew(&mut self.enemies, &mut self.am.actors, dt);
+
+            for s in steering[self.am.actors.len()..].iter() {
+                if let UnitId::Enemy(idx) = s.id {
+                    // the crew may have finished some off since steering was worked out
+                    let e = &mut self.enemies[idx];
+                    if !e.is_dead() {
+                        e.update_movement_steered(&self.train.wagons, s.steer, s.blocked, dt);
                     }
+                }
+            }
+        }
 
+        self.world.update(dt * -self.train.signed_speed());
 
-                    // for e in self.enemies.iter_mut() {
-                    //     if self.train.current_speed > 0. {
-                    //         e.set_path(&self.train.pfgrid_out, &train_origin, self.train.wagons[0].tiles[0][2].sprite.get_position());
-                    //     }
-                    //
-//                    }
+        self.train.update(dt);
 
-                    self.world.update(dt * -self.train.current_speed);
+        // save wagon's origin location before we rotate and move it
+        // because we need to use it as the actor's relative position to move and rotate them correctly
+        let first_orig = self.train.wagons[1].get_origin();
+        for wagon in self.train.wagons.iter_mut() {
+            let origin = wagon.get_origin();
 
-                    self.train.update();
+            for rail in self.world.rails.iter() {
+                if origin.x > rail.get_position().x &&
+                    origin.x < rail.get_size().x + rail.get_position().x {
+                        wagon.set_rotation(rail.get_rotation());
+                        wagon.set_position2f(origin.x, rail.get_position().y + 1.5 * TILE_SIZE_Y as f32);
+                        break;
+                    }
+            }
+        }
 
-                    // save wagon's origin location before we rotate and move it
-                    // because we need to use it as the actor's relative position to move and rotate them correctly
-                    let first_orig = self.train.wagons[1].get_origin();
-                    for wagon in self.train.wagons.iter_mut() {
-                        let origin = wagon.get_origin();
+        // the train moves under the cursor even when the mouse doesn't
+        let world_pos = self.window.map_pixel_to_coords(&self.mouse_pixel, &self.camera.game);
+        let ui_pos = self.window.map_pixel_to_coords(&self.mouse_pixel, &self.camera.ui);
+        self.inspector.update(&self.train, &self.am.actors, &self.enemies, world_pos, ui_pos);
+
+        self.stations.update(&self.train, &self.world, &mut self.rng);
+        let status = if self.events.blocked_for > 0. {
+            format!("LINE BLOCKED - CLEAR IN {}S", self.events.blocked_for.ceil() as i32)
+        } else if let Some(station) = self.stations.docked_station() {
+            format!("DOCKED AT {} - L: BUY COAL ({} AT ${})", station.name, station.coal, COAL_PRICE)
+        } else if let Some(past) = self.stations.next_station(&self.train).and_then(|s| s.overshot_by(&self.train)) {
+            format!("OVERSHOT BY {}M - R: REVERSE", (past / TILE_SIZE_X as f32) as i32)
+        } else {
+            String::new()
+        };
+        self.status_text.set_string(&status);
+
+        self.economy.update(dt, &self.stations, &mut self.train, &mut self.rng);
+        self.journal.update(&self.economy, &self.stations);
+
+        let effects = self.events.update(dt, self.economy.time, self.train.distance, &mut self.rng);
+        self.apply_event_effects(effects);
+
+        let wave = self.waves.update(self.economy.time, self.train.distance, &mut self.rng);
+        if !wave.is_empty() {
+            let points = spawn_points(wave.len(),
+                                      self.camera.visible_rect(),
+                                      &self.world,
+                                      (self.train.front_x(), self.train.rear_x()),
+                                      self.train.get_origin(),
+                                      self.train.signed_speed(),
+                                      &mut self.rng);
+            for (kind, pos) in wave.into_iter().zip(points) {
+                self.enemies.push(Enemy::new_at(&self.resources.tm, kind, pos, &self.train));
+            }
+        }
 
-                        for rail in self.world.rails.iter() {
-                            if origin.x > rail.get_position().x &&
-                                origin.x < rail.get_size().x + rail.get_position().x {
-                                    wagon.set_rotation(rail.get_rotation());
-                                    wagon.set_position2f(origin.x, rail.get_position().y + 1.5 * TILE_SIZE_Y as f32);
-                                    break;
-                                }
-                        }
-                    }
-                    self.tile_selection.set_rotation(self.train.wagons[1].rotation);
+        if let Some(ref mut mission) = self.mission {
+            for pos in mission.due_enemies(self.economy.time, self.train.get_origin()) {
+                self.enemies.push(Enemy::new_at(&self.resources.tm, EnemyKind::Bandit, pos, &self.train));
+            }
 
-                    let dest = self.train.wagons[1].get_origin();
-                    for a in self.am.actors.iter_mut() {
-                        // move actors relative to wagon position
-                        let current_pos =  a.sprite.get_position();
-                        a.sprite.set_position2f(current_pos.x + dest.x - first_orig.x,
-                                                current_pos.y + dest.y - first_orig.y);
+            if mission.update(self.economy.time, self.am.actors.len(), &self.stations).is_some() {
+                self.results.show(mission, self.economy.time, self.train.distance, self.economy.money);
+                self.state_stack.push(StateType::GameOver);
+            }
+        }
+        self.hud.update(self.camera.ui.get_size(), &self.train, &self.am.actors, &self.am.selected);
+        self.minimap.update(self.camera.ui.get_size(),
+                            &self.train,
+                            &self.world,
+                            &self.stations,
+                            &self.am.actors,
+                            &self.am.selected,
+                            &self.enemies,
+                            self.camera.visible_rect());
 
-                        // rotate around wagon origin (center) TODO: make this into a function
-                        let angle =  self.train.wagons[1].rotation - a.rotation;
-                        let angle_rad = angle * ::std::f64::consts::PI as f32 / 180.;
+        let dest = self.train.wagons[1].get_origin();
+        for a in self.am.actors.iter_mut() {
+            // move actors relative to wagon position
+            let current_pos =  a.sprite.get_position();
+            a.sprite.set_position2f(current_pos.x + dest.x - first_orig.x,
+                                    current_pos.y + dest.y - first_orig.y);
 
-                        a.sprite.move2f(-first_orig.x, -first_orig.y);
+            // rotate around wagon origin (center) TODO: make this into a function
+            let angle =  self.train.wagons[1].rotation - a.rotation;
+            let angle_rad = angle * ::std::f64::consts::PI as f32 / 180.;
 
-                        let pos = a.sprite.get_position();
+            a.sprite.move2f(-first_orig.x, -first_orig.y);
 
-                        let new = formula_rot(&pos, angle_rad);
+            let pos = a.sprite.get_position();
 
-                        a.sprite.set_position(&(first_orig + new));
+            let new = formula_rot(&pos, angle_rad);
 
-                        a.sprite.rotate(angle);
-                        a.rotation += angle;
-                    }
+            a.sprite.set_position(&(first_orig + new));
 
-                    // sounds
-                    if self.train.current_speed > 0. {
-                        {
-                            let mut train_sound = self.music_manager.get_mut(MusicId::Train);
-                            train_sound.set_volume(100. * self.train.current_speed / self.train.top_speed);
+            a.sprite.rotate(angle);
+            a.rotation += angle;
 
-                            if !self.train.moving && self.train.current_speed <= self.train.top_speed / 4. {
-                                train_sound.stop();
-                            }
-                        }
-                        {
-                            if !self.train.moving {
-                                let mut screech_sound = self.music_manager.get_mut(MusicId::Screech);
-                                screech_sound.set_volume(70. * self.train.current_speed / self.train.top_speed);
-                            }
-                        }
-                    } else {
-                        self.music_manager.get_mut(MusicId::Screech).stop();
-                    }
+            // carry the drawing start along too so only the actor's own step is interpolated
+            let prev = a.prev_pos + dest - first_orig;
+            a.prev_pos = first_orig + formula_rot(&(prev - first_orig), angle_rad);
 
-                    for e in self.enemies.iter_mut() {
-                        if !e.inside_wagon {
-                            //TODO add collision checking to this (refactor what is above into a checking function)
-                            e.sprite.move2f(dt * -self.train.current_speed, 0.);
-                        }
-                    }
+            a.animate(dt);
+        }
 
-                    // TODO: VIEW RELATED TO WAGON ROTATION & MOVEMENT
-//                    self.camera.game.set_rotation(self.train.wagons[1].rotation);
-//                    self.camera.game.set_center(&self.train.wagons[1].get_origin());
+        // sounds
+        if self.train.current_speed > 0. {
+            {
+                let mut train_sound = self.music_manager.get_mut(MusicId::Train);
+                train_sound.set_volume(100. * self.train.current_speed / self.train.top_speed);
 
+                if !self.train.moving && self.train.current_speed <= self.train.top_speed / 4. {
+                    train_sound.stop();
                 }
             }
-            _ => {}
+            {
+                if !self.train.moving {
+                    let mut screech_sound = self.music_manager.get_mut(MusicId::Screech);
+                    screech_sound.set_volume(70. * self.train.current_speed / self.train.top_speed);
+                }
+            }
+        } else {
+            self.music_manager.get_mut(MusicId::Screech).stop();
+        }
+
+        for e in self.enemies.iter_mut() {
+            if !e.inside_wagon {
+                //TODO add collision checking to this (refactor what is above into a checking function)
+                e.sprite.move2f(dt * -self.train.signed_speed(), 0.);
+            }
+
+            e.animate(dt);
         }
     }
 
@@ -578,18 +1231,24 @@ impl<'a> Game<'a> {
                 // Clear the window
                 self.window.clear(&Color::yellow());
 
+                // how far into the next step we are, things are drawn that far between the last two
+                let alpha = (self.accumulator / SIM_STEP).min(1.);
+                let mut ground = offset_states(Vector2f::new(-self.world.last_scroll * (1. - alpha), 0.));
+
                 for bg in self.world.bgs.iter() {
-                    self.window.draw(bg);
+                    self.window.draw_with_renderstates(bg, &mut ground);
                 }
 
                 for ctr in self.world.connectors.iter() {
-                    self.window.draw(ctr);
+                    self.window.draw_with_renderstates(ctr, &mut ground);
                 }
 
                 for rail in self.world.rails.iter() {
-                    self.window.draw(rail);
+                    self.window.draw_with_renderstates(rail, &mut ground);
                 }
 
+                self.stations.draw(&mut self.window, &mut ground);
+
                 for w in self.train.wagons.iter() {
                     // // view culling
                     // let wagon_bound = FloatRect::new(w.get_origin().x / 2.,
@@ -608,48 +1267,51 @@ impl<'a> Game<'a> {
                 }
 
                 for e in self.enemies.iter() {
-                    self.window.draw(&e.sprite);
+                    let offset = (e.prev_pos - e.sprite.get_position()) * (1. - alpha);
+                    self.window.draw_with_renderstates(&e.sprite, &mut offset_states(offset));
                 }
 
                 // draw all of our actors and their paths
-                self.am.draw(&mut self.window);
-
-
-               // debug pfgrid view
-                // for (i, t) in  self.train.pfgrid_in.grid.iter().enumerate() {
-                //     for (j, t) in t.iter().enumerate() {
-                //         let train_origin = self.train.get_origin();
-
-                //         let mut shape = RectangleShape::new().unwrap();
-                //         shape.set_size2f(64., 64.);
-                //         shape.set_position2f(i as f32 * 64. + train_origin.x - 2. * 64.,
-                //                              j as f32 * 64. + train_origin.y - 2. * 64.);
-
-                //         if t.walkable {
-                //             shape.set_fill_color(&Color::new_rgba(0, 255, 0, 120));
-                //         } else {
-                //             shape.set_fill_color(&Color::new_rgba(255, 0, 0, 120));
-                //         }
-
-                //         self.window.draw(&shape);
-                //     }
-                // }
-
-                if !self.am.selected.is_empty() {
-                    self.window.draw(&self.tile_selection);
+                self.am.draw(&mut self.window, alpha);
+
+
+                #[cfg(debug_assertions)]
+                self.debug.draw_world(&mut self.window, &self.train, &self.am.actors, &self.enemies);
+
+                self.inspector.draw_highlight(&mut self.window);
+
+                // ui view
+                self.window.set_view(&self.camera.ui);
+                self.manifest.draw(&mut self.window);
+                self.journal.draw(&mut self.window);
+                self.hud.draw(&mut self.window);
+                self.minimap.draw(&mut self.window);
+                self.inspector.draw_tooltip(&mut self.window);
+                #[cfg(debug_assertions)]
+                self.debug.draw_ui(&mut self.window);
+                self.scripting.draw(&mut self.window);
+                self.events.draw(&mut self.window);
+                if let Some(ref mission) = self.mission {
+                    mission.draw(&mut self.window);
                 }
-
+                self.console.draw(&mut self.window);
+                self.window.draw(&self.status_text);
                 if self.is_paused {
-                    // ui view
-                    self.window.set_view(&self.camera.ui);
                     self.window.draw(&self.paused_text);
-                    self.window.set_view(&self.camera.game);
                 }
+                self.window.set_view(&self.camera.game);
+
+            }
+            StateType::GameOver => {
+                self.window.clear(&Color::black());
 
+                self.window.set_view(&self.camera.ui);
+                self.results.draw(&mut self.window);
             }
             StateType::Menu => {
                 self.window.clear(&Color::black());
 
+                self.window.set_view(&self.camera.ui);
                 for button in &self.menu.buttons {
                     self.window.draw(&button.text);
                 }
@@ -0,0 +1,253 @@
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use resource_manager::{FontManager, FontId, TextureManager, TextureId};
+use train::Train;
+use actor::{Actor, ActorId};
+use boiler::{MAX_PRESSURE, FIREBOX_CAPACITY};
+use game_consts::*;
+
+const HUD_HEIGHT: f32 = 140.;
+const GAUGE_WIDTH: f32 = 300.;
+const PORTRAIT_SIZE: f32 = 100.;
+
+/// A horizontal bar gauge with a label on top.
+struct Gauge<'a> {
+    back: RectangleShape<'a>,
+    fill: RectangleShape<'a>,
+    label: Text<'a>,
+}
+
+impl<'a> Gauge<'a> {
+    fn new(color: &Color) -> Self {
+        let mut back = RectangleShape::new().unwrap();
+        back.set_fill_color(&Color::new_rgba(40, 40, 40, 220));
+        back.set_outline_color(&Color::white());
+        back.set_outline_thickness(1.);
+
+        let mut fill = RectangleShape::new().unwrap();
+        fill.set_fill_color(color);
+
+        Gauge {
+            back: back,
+            fill: fill,
+            label: Text::new().unwrap(),
+        }
+    }
+
+    fn init(&mut self, font: &'a Font) {
+        self.label.set_font(font);
+        self.label.set_character_size(16);
+    }
+
+    fn set(&mut self, pos: Vector2f, width: f32, fraction: f32, label: &str) {
+        self.label.set_position(&pos);
+        self.label.set_string(label);
+
+        self.back.set_position2f(pos.x, pos.y + 22.);
+        self.back.set_size2f(width, 14.);
+        self.fill.set_position2f(pos.x, pos.y + 22.);
+        self.fill.set_size2f(width * fraction.max(0.).min(1.), 14.);
+    }
+
+    fn draw(&self, window: &mut RenderWindow) {
+        window.draw(&self.back);
+        window.draw(&self.fill);
+        window.draw(&self.label);
+    }
+}
+
+/// Portrait of one actor, with its needs. Made the first time the actor gets selected
+/// and kept around, only moved and refreshed after that.
+struct Portrait<'a> {
+    actor: ActorId,
+    frame: RectangleShape<'a>,
+    sprite: Sprite<'a>,
+    hunger: RectangleShape<'a>,
+    fatigue: RectangleShape<'a>,
+    status: Text<'a>,
+}
+
+impl<'a> Portrait<'a> {
+    fn new(actor: ActorId, font: &'a Font, texture: &'a Texture) -> Self {
+        let mut frame = RectangleShape::new().unwrap();
+        frame.set_size2f(PORTRAIT_SIZE, PORTRAIT_SIZE + 20.);
+        frame.set_fill_color(&Color::new_rgba(30, 30, 30, 230));
+        frame.set_outline_color(&Color::green());
+        frame.set_outline_thickness(2.);
+
+        let mut sprite = Sprite::new_with_texture(texture).unwrap();
+        sprite.set_scale2f(2., 2.);
+
+        let mut hunger = RectangleShape::new().unwrap();
+        hunger.set_fill_color(&Color::new_rgb(255, 170, 90));
+        let mut fatigue = RectangleShape::new().unwrap();
+        fatigue.set_fill_color(&Color::new_rgb(120, 150, 255));
+
+        let mut status = Text::new().unwrap();
+        status.set_font(font);
+        status.set_character_size(11);
+
+        Portrait {
+            actor: actor,
+            frame: frame,
+            sprite: sprite,
+            hunger: hunger,
+            fatigue: fatigue,
+            status: status,
+        }
+    }
+
+    /// Moves the portrait to `(x, y)` and fills it in for `a`.
+    fn update(&mut self, x: f32, y: f32, a: &Actor) {
+        self.frame.set_position2f(x, y);
+        self.sprite.set_position2f(x + PORTRAIT_SIZE / 2. - 32., y + 4.);
+
+        // the bars show what's left, so they shrink as needs grow
+        self.hunger.set_position2f(x + 4., y + 70.);
+        self.hunger.set_size2f((PORTRAIT_SIZE - 8.) * (1. - a.needs.hunger), 6.);
+        self.fatigue.set_position2f(x + 4., y + 80.);
+        self.fatigue.set_size2f((PORTRAIT_SIZE - 8.) * (1. - a.needs.fatigue), 6.);
+
+        self.status.set_position2f(x + 4., y + 90.);
+        self.status.set_string(&match a.carrying {
+            Some((item, n)) => format!("{} {}", n, item.name()),
+            None if !a.move_seq.is_empty() => String::from("MOVING"),
+            None => String::from("IDLE"),
+        });
+    }
+}
+
+pub struct Hud<'a> {
+    background: RectangleShape<'a>,
+    speed: Gauge<'a>,
+    pressure: Gauge<'a>,
+    firebox: Gauge<'a>,
+    info: Text<'a>,
+    portraits: Vec<Portrait<'a>>,
+    /// Portraits on show, in selection order, as indices into `portraits`.
+    shown: Vec<usize>,
+    font: Option<&'a Font>,
+    portrait_texture: Option<&'a Texture>,
+}
+
+impl<'a> Hud<'a> {
+    pub fn new() -> Self {
+        let mut background = RectangleShape::new().unwrap();
+        background.set_fill_color(&Color::new_rgba(0, 0, 0, 170));
+
+        Hud {
+            background: background,
+            speed: Gauge::new(&Color::green()),
+            pressure: Gauge::new(&Color::new_rgb(230, 140, 40)),
+            firebox: Gauge::new(&Color::red()),
+            info: Text::new().unwrap(),
+            portraits: vec![],
+            shown: vec![],
+            font: None,
+            portrait_texture: None,
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager, tm: &'a TextureManager) {
+        let font = fm.get(FontId::Joystix);
+        self.speed.init(font);
+        self.pressure.init(font);
+        self.firebox.init(font);
+        self.info.set_font(font);
+        self.info.set_character_size(16);
+        self.font = Some(font);
+        self.portrait_texture = Some(tm.get(TextureId::Char0Nm));
+    }
+
+    /// Lays the hud out along the bottom of a ui view of `ui_size`.
+    pub fn update(&mut self, ui_size: Vector2f, train: &Train, actors: &[Actor], selected: &[usize]) {
+        let top = ui_size.y - HUD_HEIGHT;
+        self.background.set_position2f(0., top);
+        self.background.set_size2f(ui_size.x, HUD_HEIGHT);
+
+        let speed_label = format!("SPEED {:>4}/{}{}",
+                                  train.current_speed as i32,
+                                  train.top_speed as i32,
+                                  if train.reversing { " REV" } else { "" });
+        self.speed.set(Vector2f::new(20., top + 10.),
+                       GAUGE_WIDTH,
+                       train.current_speed / train.top_speed,
+                       &speed_label);
+        self.pressure.set(Vector2f::new(20., top + 50.),
+                          GAUGE_WIDTH,
+                          train.boiler.pressure / MAX_PRESSURE,
+                          &format!("PRESSURE {}", train.boiler.pressure as i32));
+        self.firebox.set(Vector2f::new(20., top + 90.),
+                         GAUGE_WIDTH,
+                         train.boiler.firebox / FIREBOX_CAPACITY,
+                         &format!("FIREBOX {:.1}", train.boiler.firebox));
+
+        let engine_state = if train.boiler.tractive_effort() <= 0. {
+            if train.boiler.is_dry() { "FIRE OUT" } else { "NO STEAM" }
+        } else if !train.moving {
+            if train.current_speed > 0. { "BRAKING" } else { "STOPPED" }
+        } else if train.current_speed < train.top_speed * train.boiler.tractive_effort() {
+            "ACCELERATING"
+        } else {
+            "CRUISING"
+        };
+        self.info.set_string(&format!("ODO {}M\nENGINE {}",
+                                      (train.distance / TILE_SIZE_X as f32) as i32,
+                                      engine_state));
+        self.info.set_position2f(GAUGE_WIDTH + 50., top + 10.);
+
+        self.update_portraits(top, actors, selected);
+    }
+
+    fn update_portraits(&mut self, top: f32, actors: &[Actor], selected: &[usize]) {
+        let (font, texture) = match (self.font, self.portrait_texture) {
+            (Some(font), Some(texture)) => (font, texture),
+            _ => return,
+        };
+
+        // forget the portraits of anyone who isn't around any more
+        self.portraits.retain(|p| actors.iter().any(|a| a.id == p.actor));
+
+        self.shown.clear();
+        let left = GAUGE_WIDTH + 340.;
+        for (n, &idx) in selected.iter().enumerate() {
+            let a = &actors[idx];
+            let p = match self.portraits.iter().position(|p| p.actor == a.id) {
+                Some(p) => p,
+                None => {
+                    self.portraits.push(Portrait::new(a.id, font, texture));
+                    self.portraits.len() - 1
+                }
+            };
+            self.portraits[p].update(left + n as f32 * (PORTRAIT_SIZE + 10.), top + 10., a);
+            self.shown.push(p);
+        }
+    }
+
+    /// The actor whose portrait is under `ui_pos`, if any.
+    pub fn portrait_at(&self, ui_pos: Vector2f) -> Option<ActorId> {
+        self.shown
+            .iter()
+            .map(|&p| &self.portraits[p])
+            .find(|p| p.frame.get_global_bounds().contains(ui_pos))
+            .map(|p| p.actor)
+    }
+
+    /// Draws the hud, call with the ui view set.
+    pub fn draw(&self, window: &mut RenderWindow) {
+        window.draw(&self.background);
+        self.speed.draw(window);
+        self.pressure.draw(window);
+        self.firebox.draw(window);
+        window.draw(&self.info);
+
+        for p in self.shown.iter().map(|&p| &self.portraits[p]) {
+            window.draw(&p.frame);
+            window.draw(&p.sprite);
+            window.draw(&p.hunger);
+            window.draw(&p.fatigue);
+            window.draw(&p.status);
+        }
+    }
+}
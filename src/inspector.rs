@@ -0,0 +1,107 @@
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use resource_manager::{FontManager, FontId};
+use wagon::TileType;
+use train::Train;
+use actor::{Actor, crew_name};
+use enemy::Enemy;
+use game_consts::*;
+
+/// Highlights the wagon tile under the cursor and shows what's on it.
+pub struct TileInspector<'a> {
+    /// `(wagon, row, column)` of the tile under the cursor.
+    pub hovered: Option<(usize, usize, usize)>,
+    highlight: RectangleShape<'a>,
+    background: RectangleShape<'a>,
+    text: Text<'a>,
+}
+
+impl<'a> TileInspector<'a> {
+    pub fn new() -> Self {
+        let mut highlight = RectangleShape::new().unwrap();
+        highlight.set_size2f(TILE_SIZE_X as f32, TILE_SIZE_Y as f32);
+        highlight.set_origin2f(TILE_SIZE_X as f32 / 2., TILE_SIZE_Y as f32 / 2.);
+        highlight.set_fill_color(&Color::new_rgba(255, 255, 0, 60));
+
+        let mut background = RectangleShape::new().unwrap();
+        background.set_fill_color(&Color::new_rgba(0, 0, 0, 190));
+        background.set_outline_color(&Color::yellow());
+        background.set_outline_thickness(1.);
+
+        TileInspector {
+            hovered: None,
+            highlight: highlight,
+            background: background,
+            text: Text::new().unwrap(),
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager) {
+        self.text.set_font(fm.get(FontId::Joystix));
+        self.text.set_character_size(14);
+    }
+
+    /// `world_pos` is the cursor in the game view, `ui_pos` the same cursor in the ui view.
+    pub fn update(&mut self,
+                  train: &Train,
+                  actors: &[Actor],
+                  enemies: &[Enemy],
+                  world_pos: Vector2f,
+                  ui_pos: Vector2f) {
+        self.hovered = train.tile_index_at_any(world_pos);
+
+        let (w, i, j) = match self.hovered {
+            Some(idx) => idx,
+            None => return,
+        };
+        let wagon = &train.wagons[w];
+        let tile = &wagon.tiles[i][j];
+
+        // snap to the tile itself, following the wagon around curves
+        self.highlight.set_position(&tile.sprite.get_position());
+        self.highlight.set_rotation(wagon.rotation);
+
+        let mut s = format!("{} (WAGON {} {},{})\n", tile.name(), w, i, j);
+        if let TileType::Door(_) = tile.tile_type {
+            s.push_str(if tile.is_solid { "DOOR CLOSED\n" } else { "DOOR OPEN\n" });
+        }
+        s.push_str(if tile.is_solid { "NOT WALKABLE\n" } else { "WALKABLE\n" });
+
+        // everyone standing on the tile, crew first
+        let mut occupants: Vec<String> = actors.iter()
+            .filter(|a| tile.contains(a.sprite.get_position()))
+            .map(|a| format!("{} ({})", crew_name(a.id), a.kind.name()))
+            .collect();
+        occupants.extend(enemies.iter()
+            .filter(|e| tile.contains(e.sprite.get_position()))
+            .map(|e| format!("{}{}", e.kind.name(), if e.is_dead() { " (DEAD)" } else { "" })));
+        if occupants.is_empty() {
+            s.push_str("EMPTY");
+        } else {
+            s.push_str(&occupants.join("\n"));
+        }
+
+        self.text.set_string(&s);
+        self.text.set_position2f(ui_pos.x + 24., ui_pos.y + 24.);
+
+        let bounds = self.text.get_global_bounds();
+        self.background.set_position2f(bounds.left - 8., bounds.top - 8.);
+        self.background.set_size2f(bounds.width + 16., bounds.height + 16.);
+    }
+
+    /// Draws the highlight, call with the game view set.
+    pub fn draw_highlight(&self, window: &mut RenderWindow) {
+        if self.hovered.is_some() {
+            window.draw(&self.highlight);
+        }
+    }
+
+    /// Draws the tooltip, call with the ui view set.
+    pub fn draw_tooltip(&self, window: &mut RenderWindow) {
+        if self.hovered.is_some() {
+            window.draw(&self.background);
+            window.draw(&self.text);
+        }
+    }
+}
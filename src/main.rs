@@ -7,6 +7,7 @@ extern crate sfml;
 extern crate rand;
 extern crate astar;
 extern crate lyon_bezier;
+extern crate rhai;
 
 mod state_stack;
 mod resource_manager;
@@ -24,6 +25,25 @@ mod enemy;
 mod pathfinding;
 mod train;
 mod actor_manager;
+mod cargo;
+mod boiler;
+mod station;
+mod economy;
+mod avoidance;
+mod animation;
+mod inspector;
+mod hud;
+mod minimap;
+mod console;
+mod scripting;
+mod scenario;
+mod events;
+mod waves;
+mod rng;
+mod replay;
+mod timestep;
+#[cfg(debug_assertions)]
+mod debug;
 
 mod game_consts {
     pub const TILE_SIZE_X: u32 = 64;
@@ -0,0 +1,200 @@
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use train::Train;
+use world::World;
+use station::StationManager;
+use actor::Actor;
+use enemy::Enemy;
+
+const MAP_WIDTH: f32 = 360.;
+const MAP_HEIGHT: f32 = 200.;
+/// How much of the world fits across the minimap, in world units.
+const MAP_SPAN: f32 = 9000.;
+/// Space kept free at the bottom of the ui view for the hud.
+const BOTTOM_MARGIN: f32 = 160.;
+
+/// Corner map of the train, the units around it and the track ahead.
+/// Clicking it moves the game camera to that point.
+pub struct Minimap<'a> {
+    background: RectangleShape<'a>,
+    lines: VertexArray,
+    dots: VertexArray,
+    /// World position shown at the top left corner of the map.
+    world_origin: Vector2f,
+    pub visible: bool,
+}
+
+impl<'a> Minimap<'a> {
+    pub fn new() -> Self {
+        let mut background = RectangleShape::new().unwrap();
+        background.set_size2f(MAP_WIDTH, MAP_HEIGHT);
+        background.set_fill_color(&Color::new_rgba(0, 0, 0, 190));
+        background.set_outline_color(&Color::white());
+        background.set_outline_thickness(2.);
+
+        let mut lines = VertexArray::new().unwrap();
+        lines.set_primitive_type(PrimitiveType::sfLines);
+        let mut dots = VertexArray::new().unwrap();
+        dots.set_primitive_type(PrimitiveType::sfQuads);
+
+        Minimap {
+            background: background,
+            lines: lines,
+            dots: dots,
+            world_origin: Vector2f::new(0., 0.),
+            visible: true,
+        }
+    }
+
+    fn scale(&self) -> f32 {
+        MAP_WIDTH / MAP_SPAN
+    }
+
+    fn to_map(&self, pos: Vector2f) -> Vector2f {
+        let corner = self.background.get_position();
+        Vector2f::new(corner.x + (pos.x - self.world_origin.x) * self.scale(),
+                      corner.y + (pos.y - self.world_origin.y) * self.scale())
+    }
+
+    fn on_map(&self, p: Vector2f) -> bool {
+        self.background.get_global_bounds().contains(p)
+    }
+
+    /// Draws the part of the segment that lies on the map.
+    fn line(&mut self, from: Vector2f, to: Vector2f, color: &Color) {
+        let (a, b) = (self.to_map(from), self.to_map(to));
+        if let Some((a, b)) = clip(a, b, &self.background.get_global_bounds()) {
+            self.lines.append(&Vertex::new_with_pos_color(&a, color));
+            self.lines.append(&Vertex::new_with_pos_color(&b, color));
+        }
+    }
+
+    fn dot(&mut self, pos: Vector2f, size: f32, color: &Color) {
+        let p = self.to_map(pos);
+        if !self.on_map(p) {
+            return;
+        }
+        let h = size / 2.;
+        for &(dx, dy) in [(-h, -h), (h, -h), (h, h), (-h, h)].iter() {
+            self.dots.append(&Vertex::new_with_pos_color(&Vector2f::new(p.x + dx, p.y + dy), color));
+        }
+    }
+
+    /// Rebuilds the map around the train, in the bottom right corner of a ui view of `ui_size`.
+    pub fn update(&mut self,
+                  ui_size: Vector2f,
+                  train: &Train,
+                  world: &World,
+                  stations: &StationManager,
+                  actors: &[Actor],
+                  selected: &[usize],
+                  enemies: &[Enemy],
+                  camera_rect: FloatRect) {
+        self.background.set_position2f(ui_size.x - MAP_WIDTH - 20., ui_size.y - MAP_HEIGHT - BOTTOM_MARGIN);
+
+        // keep the train a bit left of the middle, there's more to see ahead of it
+        let front = train.wagons[0].get_origin();
+        let span_y = MAP_SPAN * MAP_HEIGHT / MAP_WIDTH;
+        self.world_origin = Vector2f::new(front.x - MAP_SPAN * 0.4, front.y - span_y / 2.);
+
+        self.lines.clear();
+        self.dots.clear();
+
+        let rail_color = Color::new_rgb(160, 160, 160);
+        for rail in world.rails.iter() {
+            let from = rail.get_position();
+            let angle = rail.get_rotation().to_radians();
+            let len = rail.get_size().x;
+            let to = Vector2f::new(from.x + len * angle.cos(), from.y + len * angle.sin());
+            self.line(from, to, &rail_color);
+        }
+        // the track has no junctions yet, stations are the only thing along it worth marking
+
+        for station in stations.stations.iter() {
+            let start = station.screen_x(train);
+            let y = world.track_y_at(start).unwrap_or(front.y);
+            let color = if station.can_dock(train) { Color::green() } else { Color::yellow() };
+            self.line(Vector2f::new(start, y), Vector2f::new(start + station.length, y), &color);
+            self.dot(Vector2f::new(start, y), 6., &color);
+        }
+
+        for wagon in train.wagons.iter() {
+            let (rows, cols) = (wagon.tiles.len() - 1, wagon.tiles[0].len() - 1);
+            let corners = [wagon.tiles[0][0].sprite.get_position(),
+                           wagon.tiles[0][cols].sprite.get_position(),
+                           wagon.tiles[rows][cols].sprite.get_position(),
+                           wagon.tiles[rows][0].sprite.get_position()];
+            for k in 0..4 {
+                self.line(corners[k], corners[(k + 1) % 4], &Color::white());
+            }
+        }
+
+        for (idx, a) in actors.iter().enumerate() {
+            let color = if selected.contains(&idx) { Color::green() } else { Color::new_rgb(40, 160, 255) };
+            self.dot(a.sprite.get_position(), 4., &color);
+        }
+        for e in enemies.iter() {
+            self.dot(e.sprite.get_position(), 4., &Color::red());
+        }
+
+        // what the game view is looking at
+        let (l, t, r, b) = (camera_rect.left,
+                            camera_rect.top,
+                            camera_rect.left + camera_rect.width,
+                            camera_rect.top + camera_rect.height);
+        let view_color = Color::new_rgba(255, 255, 255, 120);
+        self.line(Vector2f::new(l, t), Vector2f::new(r, t), &view_color);
+        self.line(Vector2f::new(r, t), Vector2f::new(r, b), &view_color);
+        self.line(Vector2f::new(r, b), Vector2f::new(l, b), &view_color);
+        self.line(Vector2f::new(l, b), Vector2f::new(l, t), &view_color);
+    }
+
+    /// The world point under `ui_pos` if it's on the map.
+    pub fn world_at(&self, ui_pos: Vector2f) -> Option<Vector2f> {
+        if !self.visible || !self.on_map(ui_pos) {
+            return None;
+        }
+        let corner = self.background.get_position();
+        Some(Vector2f::new(self.world_origin.x + (ui_pos.x - corner.x) / self.scale(),
+                           self.world_origin.y + (ui_pos.y - corner.y) / self.scale()))
+    }
+
+    /// Draws the map, call with the ui view set.
+    pub fn draw(&self, window: &mut RenderWindow) {
+        if self.visible {
+            window.draw(&self.background);
+            window.draw(&self.lines);
+            window.draw(&self.dots);
+        }
+    }
+}
+
+/// Cuts the segment `a`-`b` down to the part inside `rect` (Liang-Barsky), `None` if it misses.
+fn clip(a: Vector2f, b: Vector2f, rect: &FloatRect) -> Option<(Vector2f, Vector2f)> {
+    let d = b - a;
+    let (mut t0, mut t1) = (0f32, 1f32);
+    let edges = [(-d.x, a.x - rect.left),
+                 (d.x, rect.left + rect.width - a.x),
+                 (-d.y, a.y - rect.top),
+                 (d.y, rect.top + rect.height - a.y)];
+    for &(p, q) in edges.iter() {
+        if p == 0. {
+            // parallel to this edge, either fully outside or not limited by it
+            if q < 0. {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0. {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some((a + d * t0, a + d * t1))
+}
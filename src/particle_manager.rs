@@ -5,54 +5,74 @@ use rand::Rng;
 use sfml::graphics::*;
 use sfml::system::*;
 
-pub struct Particle<'a> {
-    pub shape: RectangleShape<'a>,
+use rng::GameRng;
+use wagon::formula_rot;
+
+/// Side length of a particle at scale 1.
+const PARTICLE_SIZE: f32 = 20.;
+
+pub struct Particle {
+    pub position: Vector2f,
+    /// Degrees.
+    pub rotation: f32,
+    pub scale: f32,
+    pub color: Color,
     pub direction: Vector2f,
     pub mark_for_explosion: bool,
 }
 
-impl<'a> Particle<'a> {
-    fn new(shape: RectangleShape<'a>, direction: Vector2f) -> Self {
+impl Particle {
+    fn new(position: Vector2f, color: Color, direction: Vector2f) -> Self {
         Particle {
-            shape: shape,
+            position: position,
+            rotation: 0.,
+            scale: 1.,
+            color: color,
             direction: direction,
             mark_for_explosion: false,
         }
     }
 }
 
-pub struct ParticleManager<'a> {
-    pub particles: Vec<Particle<'a>>,
+pub struct ParticleManager {
+    pub particles: Vec<Particle>,
     position: Vector2f,
     pub clock: Clock,
     cleanup: Vec<usize>,
+    /// One quad per particle, rebuilt every update so they all go out in a single draw call.
+    vertices: VertexArray,
 }
 
-impl<'a> ParticleManager<'a> {
+impl ParticleManager {
     pub fn new() -> Self {
+        let mut vertices = VertexArray::new().unwrap();
+        vertices.set_primitive_type(PrimitiveType::sfQuads);
+
         ParticleManager {
             particles: vec![],
             position: Vector2f::new(0., 0.),
             clock: Clock::new(),
             cleanup: vec![],
+            vertices: vertices,
         }
     }
 
     pub fn update(&mut self, dt: f32, downwards_speed: f32) {
         for (i, p) in self.particles.iter_mut().enumerate() {
-            if p.shape.get_scale().x <= 0.05 {
+            if p.scale <= 0.05 {
                 self.cleanup.push(i);
             } else if p.mark_for_explosion {
-                if p.shape.get_scale().x >= 1.1 {
+                if p.scale >= 1.1 {
                     self.cleanup.push(i);
                 } else {
-                    p.shape.move2f(0., downwards_speed);
-                    p.shape.scale2f(1.1, 1.1);
+                    p.position.y += downwards_speed;
+                    p.scale *= 1.1;
                 }
             } else {
-                p.shape.move2f(p.direction.x * dt, p.direction.y * dt);
-                p.shape.rotate(90. * dt);
-                p.shape.scale2f(0.95, 0.95);
+                p.position.x += p.direction.x * dt;
+                p.position.y += p.direction.y * dt;
+                p.rotation += 90. * dt;
+                p.scale *= 0.95;
             }
         }
 
@@ -60,39 +80,50 @@ impl<'a> ParticleManager<'a> {
             self.particles.remove(*i);
         }
         self.cleanup.clear();
+
+        self.rebuild_vertices();
     }
 
-    pub fn set_position(&mut self, position: &Vector2f) {
-        self.position = *position;
+    fn rebuild_vertices(&mut self) {
+        self.vertices.clear();
+        let h = PARTICLE_SIZE / 2.;
+        for p in self.particles.iter() {
+            let angle = p.rotation.to_radians();
+            for &(dx, dy) in [(-h, -h), (h, -h), (h, h), (-h, h)].iter() {
+                let corner = formula_rot(&Vector2f::new(dx * p.scale, dy * p.scale), angle);
+                self.vertices.append(&Vertex::new_with_pos_color(&(p.position + corner), &p.color));
+            }
+        }
     }
 
-    fn create_particle_shape(position: &Vector2f, color: &Color) -> RectangleShape<'a> {
-        let mut shape = RectangleShape::new().unwrap();
-        shape.set_position(position);
-        shape.set_size(&Vector2f::new(20., 20.));
-        shape.set_origin(&Vector2f::new(10., 10.));
-        shape.set_fill_color(color);
-        shape
+    pub fn set_position(&mut self, position: &Vector2f) {
+        self.position = *position;
     }
 
-    pub fn spawn_random_particle(&mut self, color: &Color) {
-        let shape = ParticleManager::create_particle_shape(&self.position, color);
-        let direction = Vector2f::new(rand::thread_rng().gen_range(-400, 400) as f32,
-                                      rand::thread_rng().gen_range(-500, -200) as f32);
-        self.particles.push(Particle::new(shape, direction));
+    pub fn spawn_random_particle(&mut self, color: &Color, rng: &mut GameRng) {
+        let direction = Vector2f::new(rng.gen_range(-400, 400) as f32,
+                                      rng.gen_range(-500, -200) as f32);
+        self.particles.push(Particle::new(self.position, *color, direction));
     }
 
     pub fn spawn_directed_particle(&mut self, color: &Color, dir: &Vector2f, is_big: &bool) {
-        let mut shape = ParticleManager::create_particle_shape(&self.position, color);
+        let mut particle = Particle::new(self.position, *color, *dir);
 
         if *is_big {
-            shape.set_scale2f(2., 2.);
+            particle.scale = 2.;
         }
 
-        self.particles.push(Particle::new(shape, *dir));
+        self.particles.push(particle);
     }
 
     pub fn reset(&mut self) {
         self.particles.clear();
+        self.vertices.clear();
+    }
+}
+
+impl Drawable for ParticleManager {
+    fn draw<RT: RenderTarget>(&self, render_target: &mut RT, render_states: &mut RenderStates) {
+        render_target.draw_with_renderstates(&self.vertices, render_states);
     }
 }
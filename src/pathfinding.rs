@@ -4,7 +4,7 @@ use astar::*;
 use std::vec::IntoIter;
 use sfml::graphics::{FloatRect, Transformable};
 use wagon::{Wagon, TileType};
-use std::collections::VecDeque;
+use std::collections::{VecDeque, HashSet};
 use std::thread;
 
 #[derive(Copy, Clone)]
@@ -27,6 +27,53 @@ impl PathfindingGrid {
     }
 }
 
+/// Converts a world position to grid coordinates. The grid's top left corner sits
+/// `padding` tiles up and left of `train_pos`.
+pub fn world_to_grid(pos: Vector2f, grid: &PathfindingGrid, train_pos: Vector2f) -> (i32, i32) {
+    let left = train_pos.x - grid.padding.2 as f32 * TILE_SIZE_X as f32;
+    let top = train_pos.y - grid.padding.0 as f32 * TILE_SIZE_Y as f32;
+    (((pos.x - left) / TILE_SIZE_X as f32).floor() as i32,
+     ((pos.y - top) / TILE_SIZE_Y as f32).floor() as i32)
+}
+
+/// Converts grid coordinates to the world position of that tile's center.
+pub fn grid_to_world(tile: (i32, i32), grid: &PathfindingGrid, train_pos: Vector2f) -> Vector2f {
+    let left = train_pos.x - grid.padding.2 as f32 * TILE_SIZE_X as f32;
+    let top = train_pos.y - grid.padding.0 as f32 * TILE_SIZE_Y as f32;
+    Vector2f::new(left + (tile.0 as f32 + 0.5) * TILE_SIZE_X as f32,
+                  top + (tile.1 as f32 + 0.5) * TILE_SIZE_Y as f32)
+}
+
+/// Finds the walkable tile closest to `from` that isn't in `taken`, searching outwards ring by ring.
+pub fn nearest_free_tile(grid: &PathfindingGrid, from: (i32, i32), taken: &HashSet<(i32, i32)>) -> Option<(i32, i32)> {
+    if grid.grid.is_empty() {
+        return None;
+    }
+
+    let (w, h) = (grid.grid.len() as i32, grid.grid[0].len() as i32);
+    let from = (from.0.max(0).min(w - 1), from.1.max(0).min(h - 1));
+
+    let mut visited = vec![vec![false; h as usize]; w as usize];
+    let mut queue = VecDeque::new();
+    visited[from.0 as usize][from.1 as usize] = true;
+    queue.push_back(from);
+
+    while let Some((x, y)) = queue.pop_front() {
+        if grid.grid.is_walkable(x, y) && !taken.contains(&(x, y)) {
+            return Some((x, y));
+        }
+
+        for &(dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)].iter() {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx >= 0 && ny >= 0 && nx < w && ny < h && !visited[nx as usize][ny as usize] {
+                visited[nx as usize][ny as usize] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    None
+}
+
 pub trait Walkable {
     fn is_walkable(&self, x: i32, y: i32) -> bool;
 }
@@ -98,6 +145,9 @@ pub trait Pathfinding {
     fn move2f(&mut self, x: f32, y: f32);
     fn set_inside_wagon(&mut self, inside: bool);
 
+    fn get_speed_factor(&self) -> f32 {
+        1.
+    }
 
     fn set_path(&mut self, path: &mut VecDeque<(i32, i32)>, train_pos: Vector2f) {
         self.clear_steps();
@@ -114,14 +164,8 @@ pub trait Pathfinding {
     }
 
     fn number_of_steps_to(&self, grid: &PathfindingGrid, train_pos: &Vector2f, click_pos: Vector2f) -> Option<usize> {
-        let start = (self.get_pos().x as i32 / TILE_SIZE_X as i32
-                     - (train_pos.x - grid.padding.2 as f32 * TILE_SIZE_X as f32) as i32 / TILE_SIZE_X as i32,
-
-                     self.get_pos().y as i32 / TILE_SIZE_Y as i32
-                     - (train_pos.y - grid.padding.0 as f32 * TILE_SIZE_X as f32) as i32 / TILE_SIZE_Y as i32);
-
-        let end = (click_pos.x as i32 / TILE_SIZE_X as i32 - (train_pos.x - 2. * 64.) as i32 / TILE_SIZE_X as i32,
-                   click_pos.y as i32 / TILE_SIZE_Y as i32 - (train_pos.y - 2. * 64.) as i32 / TILE_SIZE_Y as i32);
+        let start = world_to_grid(self.get_pos(), grid, *train_pos);
+        let end = world_to_grid(click_pos, grid, *train_pos);
 
         let mut ts = GridSearch::new(grid, start, end);
 
@@ -136,22 +180,25 @@ pub trait Pathfinding {
     }
 
     fn check_collision(desired_pos: &FloatRect, wagons: &Vec<Wagon>) -> bool {
-        let mut ok_to_move = true;
+        // bounds sit at most a tile right of and below their tile's position, so only
+        // tiles positioned that close to the rect can be hit
+        let reach = FloatRect::new(desired_pos.left - TILE_SIZE_X as f32,
+                                   desired_pos.top - TILE_SIZE_Y as f32,
+                                   desired_pos.width + TILE_SIZE_X as f32,
+                                   desired_pos.height + TILE_SIZE_Y as f32);
+
         for w in wagons.iter() {
-            for t in w.tiles.iter() {
-                for t in t.iter() {
+            let (rows, columns) = w.tiles_centered_in(&reach);
+            for i in rows {
+                for j in columns.clone() {
+                    let t = &w.tiles[i][j];
                     if !t.is_solid {
                         continue;
                     }
                     for b in t.bounds.iter() {
                         if let Some(b) = *b {
-                            if let Some(_) = desired_pos.intersects(
-                                &FloatRect::new(b.left + t.sprite.get_position().x,
-                                                b.top + t.sprite.get_position().y,
-                                                b.width,
-                                                b.height)) {
-                                ok_to_move = false;
-                                break;
+                            if let Some(_) = desired_pos.intersects(&t.collision_box(&b)) {
+                                return false;
                             }
                         }
                     }
@@ -159,11 +206,24 @@ pub trait Pathfinding {
             }
         }
 
-        ok_to_move
+        true
     }
 
     fn update_movement(&mut self, wagons: &Vec<Wagon>, dt: f32) {
-        if self.steps_are_empty() {
+        self.update_movement_steered(wagons, Vector2f::new(0., 0.), false, dt);
+    }
+
+    /// Like `update_movement` but nudged by `steer` to keep clear of other units.
+    /// A `blocked` unit doesn't follow its path this frame, it only gets pushed around.
+    fn update_movement_steered(&mut self, wagons: &Vec<Wagon>, steer: Vector2f, blocked: bool, dt: f32) {
+        // how much to move per frame
+        let mult = 300. * self.get_speed_factor() * dt;
+
+        if self.steps_are_empty() || blocked {
+            // standing around, just make room
+            if steer != Vector2f::new(0., 0.) {
+                self.try_move(mult * steer.x / 2., mult * steer.y / 2., wagons);
+            }
             return;
         }
 
@@ -178,15 +238,19 @@ pub trait Pathfinding {
         let mut move_dir = Vector2f::new(dest.x - current_pos.x, dest.y - current_pos.y);
         let vec_len = (move_dir.x.powi(2) + move_dir.y.powi(2)).sqrt().abs();
 
-        move_dir.x = move_dir.x / vec_len;
-        move_dir.y = move_dir.y / vec_len;
+        move_dir.x = move_dir.x / vec_len + steer.x;
+        move_dir.y = move_dir.y / vec_len + steer.y;
 
-        let (dx, dy) = {
-            // how much to move per frame
-            let mult = 300. * dt;
-            (mult * move_dir.x, mult * move_dir.y)
-        };
+        let vec_len = (move_dir.x.powi(2) + move_dir.y.powi(2)).sqrt().abs();
+        if vec_len == 0. {
+            return;
+        }
 
+        self.try_move(mult * move_dir.x / vec_len, mult * move_dir.y / vec_len, wagons);
+    }
+
+    /// Moves by `(dx, dy)` unless that runs into a wall.
+    fn try_move(&mut self, dx: f32, dy: f32, wagons: &Vec<Wagon>) {
         if (dx, dy) != (0., 0.) {
             let actor_bounds = self.get_collision_bounds();
             let desired_pos = FloatRect::new(actor_bounds.left + dx,
@@ -198,22 +262,18 @@ pub trait Pathfinding {
                 self.move2f(dx, dy);
 
                 // check and mark if player is inside or outside the train after a succesful move
-                self.set_inside_wagon(false);
-                for w in wagons.iter() {
-                    for t in w.tiles.iter() {
-                        for t in t.iter() {
-                            if !t.is_solid &&
-                                t.sprite.get_global_bounds().contains(self.get_pos()) {
-                                    if let TileType::Door(_) = t.tile_type {
-                                        continue;
-                                    } else {
-                                        self.set_inside_wagon(true);
-                                        break;
-                                    }
-                                }
+                let pos = self.get_pos();
+                let inside = wagons.iter().any(|w| match w.tile_index_at(pos) {
+                    Some((i, j)) => {
+                        let t = &w.tiles[i][j];
+                        match t.tile_type {
+                            TileType::Door(_) => false,
+                            _ => !t.is_solid,
                         }
                     }
-                }
+                    None => false,
+                });
+                self.set_inside_wagon(inside);
             }
         }
     }
@@ -221,14 +281,8 @@ pub trait Pathfinding {
 
 
 pub fn compute_path(start: Vector2f, grid: PathfindingGrid, train_pos: Vector2f, click_pos: Vector2f) -> Option<VecDeque<(i32, i32)>> {
-    let start = (start.x as i32 / TILE_SIZE_X as i32
-                 - (train_pos.x - grid.padding.2 as f32 * TILE_SIZE_X as f32) as i32 / TILE_SIZE_X as i32,
-                 start.y as i32 / TILE_SIZE_Y as i32
-                 - (train_pos.y - grid.padding.0 as f32 * TILE_SIZE_X as f32) as i32 / TILE_SIZE_Y as i32);
-
-    //                                                    vvvvvvvvvvvvvvvvvvvvvv grid.padding?
-    let end = (click_pos.x as i32 / TILE_SIZE_X as i32 - (train_pos.x - 2. * 64.) as i32 / TILE_SIZE_X as i32,
-               click_pos.y as i32 / TILE_SIZE_Y as i32 - (train_pos.y - 2. * 64.) as i32 / TILE_SIZE_Y as i32);
+    let start = world_to_grid(start, &grid, train_pos);
+    let end = world_to_grid(click_pos, &grid, train_pos);
 
 
     let mut ts = GridSearch::new(&grid, start, end);
@@ -0,0 +1,470 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+
+use sfml::graphics::RenderWindow;
+use sfml::system::Vector2i;
+use sfml::window::*;
+
+/// Keys the game reacts to, with the names they're saved under. Anything else
+/// does nothing in game, so it isn't recorded either.
+const KEYS: [(Key, &'static str); 32] = [(Key::Escape, "Escape"),
+                                         (Key::Space, "Space"),
+                                         (Key::Return, "Return"),
+                                         (Key::BackSpace, "BackSpace"),
+                                         (Key::Tab, "Tab"),
+                                         (Key::Tilde, "Tilde"),
+                                         (Key::Up, "Up"),
+                                         (Key::C, "C"),
+                                         (Key::G, "G"),
+                                         (Key::H, "H"),
+                                         (Key::J, "J"),
+                                         (Key::L, "L"),
+                                         (Key::M, "M"),
+                                         (Key::N, "N"),
+                                         (Key::R, "R"),
+                                         (Key::V, "V"),
+                                         (Key::Num1, "Num1"),
+                                         (Key::Num2, "Num2"),
+                                         (Key::Num3, "Num3"),
+                                         (Key::Num4, "Num4"),
+                                         (Key::Num5, "Num5"),
+                                         (Key::Num6, "Num6"),
+                                         (Key::Num7, "Num7"),
+                                         (Key::Num8, "Num8"),
+                                         (Key::Num9, "Num9"),
+                                         (Key::F1, "F1"),
+                                         (Key::F2, "F2"),
+                                         (Key::F3, "F3"),
+                                         (Key::F4, "F4"),
+                                         (Key::F5, "F5"),
+                                         (Key::F6, "F6"),
+                                         (Key::F11, "F11")];
+
+const BUTTONS: [(MouseButton, &'static str); 3] = [(MouseButton::Left, "Left"),
+                                                    (MouseButton::Right, "Right"),
+                                                    (MouseButton::Middle, "Middle")];
+
+fn key_name(code: Key) -> Option<&'static str> {
+    KEYS.iter().find(|&&(k, _)| k == code).map(|&(_, name)| name)
+}
+
+fn key_named(name: &str) -> Option<Key> {
+    KEYS.iter().find(|&&(_, n)| n == name).map(|&(k, _)| k)
+}
+
+fn button_name(button: MouseButton) -> Option<&'static str> {
+    BUTTONS.iter().find(|&&(b, _)| b == button).map(|&(_, name)| name)
+}
+
+fn button_named(name: &str) -> Option<MouseButton> {
+    BUTTONS.iter().find(|&&(_, n)| n == name).map(|&(b, _)| b)
+}
+
+/// One thing the player did. These are the window events the game handles, in a form
+/// that can be written to a replay file and turned back into the same event.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputAction {
+    Closed,
+    Resized(u32, u32),
+    /// The key and whether control was held.
+    KeyPressed(Key, bool),
+    KeyReleased(Key, bool),
+    TextEntered(char),
+    MouseMoved(i32, i32),
+    MousePressed(MouseButton, i32, i32),
+    MouseReleased(MouseButton, i32, i32),
+    Wheel(i32, i32, i32),
+}
+
+impl InputAction {
+    pub fn from_event(event: &event::Event) -> Option<InputAction> {
+        match *event {
+            event::Closed => Some(InputAction::Closed),
+            event::Resized { width, height } => Some(InputAction::Resized(width, height)),
+            event::KeyPressed { code, ctrl, .. } => key_name(code).map(|_| InputAction::KeyPressed(code, ctrl)),
+            event::KeyReleased { code, ctrl, .. } => key_name(code).map(|_| InputAction::KeyReleased(code, ctrl)),
+            event::TextEntered { code } => Some(InputAction::TextEntered(code)),
+            event::MouseMoved { x, y } => Some(InputAction::MouseMoved(x, y)),
+            event::MouseButtonPressed { button, x, y } => {
+                button_name(button).map(|_| InputAction::MousePressed(button, x, y))
+            }
+            event::MouseButtonReleased { button, x, y } => {
+                button_name(button).map(|_| InputAction::MouseReleased(button, x, y))
+            }
+            event::MouseWheelMoved { delta, x, y } => Some(InputAction::Wheel(delta, x, y)),
+            _ => None,
+        }
+    }
+
+    pub fn to_event(&self) -> event::Event {
+        match *self {
+            InputAction::Closed => event::Closed,
+            InputAction::Resized(width, height) => event::Resized { width: width, height: height },
+            InputAction::KeyPressed(code, ctrl) => {
+                event::KeyPressed { code: code, alt: false, ctrl: ctrl, shift: false, system: false }
+            }
+            InputAction::KeyReleased(code, ctrl) => {
+                event::KeyReleased { code: code, alt: false, ctrl: ctrl, shift: false, system: false }
+            }
+            InputAction::TextEntered(code) => event::TextEntered { code: code },
+            InputAction::MouseMoved(x, y) => event::MouseMoved { x: x, y: y },
+            InputAction::MousePressed(button, x, y) => event::MouseButtonPressed { button: button, x: x, y: y },
+            InputAction::MouseReleased(button, x, y) => event::MouseButtonReleased { button: button, x: x, y: y },
+            InputAction::Wheel(delta, x, y) => event::MouseWheelMoved { delta: delta, x: x, y: y },
+        }
+    }
+
+    fn write(&self) -> String {
+        let ctrl = |ctrl: bool| if ctrl { " ctrl" } else { "" };
+        match *self {
+            InputAction::Closed => String::from("closed"),
+            InputAction::Resized(w, h) => format!("resized {} {}", w, h),
+            InputAction::KeyPressed(code, c) => format!("key_pressed {}{}", key_name(code).unwrap(), ctrl(c)),
+            InputAction::KeyReleased(code, c) => format!("key_released {}{}", key_name(code).unwrap(), ctrl(c)),
+            // written as a number so spaces and such survive
+            InputAction::TextEntered(code) => format!("text {}", code as u32),
+            InputAction::MouseMoved(x, y) => format!("mouse_moved {} {}", x, y),
+            InputAction::MousePressed(b, x, y) => format!("mouse_pressed {} {} {}", button_name(b).unwrap(), x, y),
+            InputAction::MouseReleased(b, x, y) => format!("mouse_released {} {} {}", button_name(b).unwrap(), x, y),
+            InputAction::Wheel(delta, x, y) => format!("wheel {} {} {}", delta, x, y),
+        }
+    }
+
+    fn parse(words: &[&str]) -> Result<InputAction, String> {
+        let key = |n: usize| words.get(n).and_then(|w| key_named(w)).ok_or(format!("bad key in {}", words.join(" ")));
+        let button = |n: usize| {
+            words.get(n).and_then(|w| button_named(w)).ok_or(format!("bad button in {}", words.join(" ")))
+        };
+        let ctrl = words.get(2) == Some(&"ctrl");
+        match words[0] {
+            "closed" => Ok(InputAction::Closed),
+            "resized" => Ok(InputAction::Resized(num(words, 1)?, num(words, 2)?)),
+            "key_pressed" => Ok(InputAction::KeyPressed(key(1)?, ctrl)),
+            "key_released" => Ok(InputAction::KeyReleased(key(1)?, ctrl)),
+            "text" => {
+                ::std::char::from_u32(num(words, 1)?).map(InputAction::TextEntered).ok_or(String::from("bad character"))
+            }
+            "mouse_moved" => Ok(InputAction::MouseMoved(num(words, 1)?, num(words, 2)?)),
+            "mouse_pressed" => Ok(InputAction::MousePressed(button(1)?, num(words, 2)?, num(words, 3)?)),
+            "mouse_released" => Ok(InputAction::MouseReleased(button(1)?, num(words, 2)?, num(words, 3)?)),
+            "wheel" => Ok(InputAction::Wheel(num(words, 1)?, num(words, 2)?, num(words, 3)?)),
+            other => Err(format!("unknown action {}", other)),
+        }
+    }
+}
+
+/// The bits of input the game polls instead of getting events for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct InputState {
+    /// Cursor in window pixels.
+    pub mouse: Vector2i,
+    pub shift: bool,
+    pub ctrl: bool,
+    pub middle_mouse: bool,
+    /// The keys that turn a right click into a patrol, follow or guard order.
+    pub p: bool,
+    pub f: bool,
+    pub t: bool,
+    /// Which way the arrow keys point, -1 to 1 on each axis.
+    pub arrows: Vector2i,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        InputState {
+            mouse: Vector2i::new(0, 0),
+            shift: false,
+            ctrl: false,
+            middle_mouse: false,
+            p: false,
+            f: false,
+            t: false,
+            arrows: Vector2i::new(0, 0),
+        }
+    }
+
+    /// What the keyboard and mouse are doing right now.
+    pub fn poll(window: &RenderWindow) -> Self {
+        let axis = |neg: Key, pos: Key| pos.is_pressed() as i32 - neg.is_pressed() as i32;
+        InputState {
+            mouse: window.get_mouse_position(),
+            shift: Key::LShift.is_pressed() || Key::RShift.is_pressed(),
+            ctrl: Key::LControl.is_pressed() || Key::RControl.is_pressed(),
+            middle_mouse: MouseButton::Middle.is_pressed(),
+            p: Key::P.is_pressed(),
+            f: Key::F.is_pressed(),
+            t: Key::T.is_pressed(),
+            arrows: Vector2i::new(axis(Key::Left, Key::Right), axis(Key::Up, Key::Down)),
+        }
+    }
+
+    fn write(&self) -> String {
+        let held: String = [(self.shift, 'S'),
+                            (self.ctrl, 'C'),
+                            (self.middle_mouse, 'M'),
+                            (self.p, 'P'),
+                            (self.f, 'F'),
+                            (self.t, 'T'),
+                            (self.arrows.x < 0, '<'),
+                            (self.arrows.x > 0, '>'),
+                            (self.arrows.y < 0, '^'),
+                            (self.arrows.y > 0, 'v')]
+            .iter()
+            .filter(|&&(on, _)| on)
+            .map(|&(_, c)| c)
+            .collect();
+        format!("{} {} {}", self.mouse.x, self.mouse.y, if held.is_empty() { "-" } else { &held })
+    }
+
+    fn parse(words: &[&str]) -> Result<InputState, String> {
+        let held = words.get(2).cloned().unwrap_or("-");
+        Ok(InputState {
+            mouse: Vector2i::new(num(words, 0)?, num(words, 1)?),
+            shift: held.contains('S'),
+            ctrl: held.contains('C'),
+            middle_mouse: held.contains('M'),
+            p: held.contains('P'),
+            f: held.contains('F'),
+            t: held.contains('T'),
+            arrows: Vector2i::new(held.contains('>') as i32 - held.contains('<') as i32,
+                                  held.contains('v') as i32 - held.contains('^') as i32),
+        })
+    }
+}
+
+/// Everything that goes into one tick of the game: how long it was and what the player did.
+pub struct Frame {
+    pub dt: f32,
+    pub input: InputState,
+    pub actions: Vec<InputAction>,
+}
+
+fn num<T: ::std::str::FromStr>(words: &[&str], n: usize) -> Result<T, String> {
+    let w = words.get(n).ok_or(format!("missing value in {}", words.join(" ")))?;
+    w.parse().map_err(|_| format!("bad number {}", w))
+}
+
+/// Writes every tick to a replay file. The file starts with the seed and scenario, then
+/// each tick gets a `tick n dt mouse_x mouse_y held` line followed by its actions.
+pub struct Recorder {
+    out: BufWriter<File>,
+    tick: u64,
+}
+
+impl Recorder {
+    pub fn create(path: &str, seed: u32, scenario: &str) -> Result<Recorder, String> {
+        let file = File::create(path).map_err(|e| format!("can't write {}: {}", path, e))?;
+        let mut out = BufWriter::new(file);
+        write!(out, "seed {}\nscenario {}\n", seed, scenario).map_err(|e| e.to_string())?;
+        Ok(Recorder {
+            out: out,
+            tick: 0,
+        })
+    }
+
+    pub fn record(&mut self, frame: &Frame) {
+        let mut s = format!("tick {} {} {}\n", self.tick, frame.dt, frame.input.write());
+        for action in frame.actions.iter() {
+            s.push_str(&action.write());
+            s.push('\n');
+        }
+        if let Err(e) = self.out.write_all(s.as_bytes()) {
+            println!("can't write replay: {}", e);
+        }
+        self.tick += 1;
+    }
+}
+
+/// A recorded game being played back tick by tick.
+pub struct Playback {
+    pub seed: u32,
+    pub scenario: String,
+    frames: VecDeque<Frame>,
+}
+
+impl Playback {
+    pub fn load(path: &str) -> Result<Playback, String> {
+        let mut source = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut source))
+            .map_err(|e| format!("can't read {}: {}", path, e))?;
+        Playback::parse(&source).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn parse(source: &str) -> Result<Playback, String> {
+        let mut seed = None;
+        let mut scenario = None;
+        let mut frames: VecDeque<Frame> = VecDeque::new();
+
+        for (n, line) in source.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let line_err = |e: String| format!("line {}: {}", n + 1, e);
+
+            match words[0] {
+                "seed" => seed = Some(num(&words, 1).map_err(&line_err)?),
+                "scenario" => scenario = Some(words[1..].join(" ")),
+                "tick" => {
+                    frames.push_back(Frame {
+                        dt: num(&words, 2).map_err(&line_err)?,
+                        input: InputState::parse(&words[3..]).map_err(&line_err)?,
+                        actions: vec![],
+                    });
+                }
+                _ => {
+                    let action = InputAction::parse(&words).map_err(&line_err)?;
+                    match frames.back_mut() {
+                        Some(frame) => frame.actions.push(action),
+                        None => return Err(line_err(String::from("action before the first tick"))),
+                    }
+                }
+            }
+        }
+
+        Ok(Playback {
+            seed: seed.ok_or(String::from("no seed"))?,
+            scenario: scenario.ok_or(String::from("no scenario"))?,
+            frames: frames,
+        })
+    }
+
+    /// The next recorded tick, `None` once the replay is over.
+    pub fn next_frame(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+}
+
+/// Whether input comes from the player, gets recorded too, or comes from a replay.
+pub enum ReplayMode {
+    Off,
+    Recording(Recorder),
+    Playing(Playback),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(action: InputAction) -> InputAction {
+        let line = action.write();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        InputAction::parse(&words).unwrap()
+    }
+
+    #[test]
+    fn actions_round_trip() {
+        let mut actions = vec![InputAction::Closed,
+                               InputAction::Resized(1600, 900),
+                               InputAction::TextEntered('a'),
+                               InputAction::TextEntered(' '),
+                               InputAction::TextEntered('\n'),
+                               InputAction::TextEntered('é'),
+                               InputAction::MouseMoved(-12, 340),
+                               InputAction::Wheel(-1, 5, 6),
+                               InputAction::Wheel(3, 0, 0)];
+        for &(key, _) in KEYS.iter() {
+            for &ctrl in [false, true].iter() {
+                actions.push(InputAction::KeyPressed(key, ctrl));
+                actions.push(InputAction::KeyReleased(key, ctrl));
+            }
+        }
+        for &(button, _) in BUTTONS.iter() {
+            actions.push(InputAction::MousePressed(button, 10, -20));
+            actions.push(InputAction::MouseReleased(button, 0, 900));
+        }
+
+        for action in actions {
+            assert_eq!(round_trip(action), action, "{}", action.write());
+        }
+    }
+
+    #[test]
+    fn bad_actions_are_errors() {
+        assert!(InputAction::parse(&["jump"]).is_err());
+        assert!(InputAction::parse(&["key_pressed", "Q"]).is_err());
+        assert!(InputAction::parse(&["mouse_pressed", "Back", "1", "2"]).is_err());
+        assert!(InputAction::parse(&["resized", "1600"]).is_err());
+        assert!(InputAction::parse(&["text", "55296"]).is_err());
+    }
+
+    #[test]
+    fn held_keys_round_trip() {
+        // every combination of the flags, with the arrows on each side of each axis
+        for bits in 0..64 {
+            for x in -1..2 {
+                for y in -1..2 {
+                    let state = InputState {
+                        mouse: Vector2i::new(x * 100, y * 50),
+                        shift: bits & 1 != 0,
+                        ctrl: bits & 2 != 0,
+                        middle_mouse: bits & 4 != 0,
+                        p: bits & 8 != 0,
+                        f: bits & 16 != 0,
+                        t: bits & 32 != 0,
+                        arrows: Vector2i::new(x, y),
+                    };
+                    let line = state.write();
+                    let words: Vec<&str> = line.split_whitespace().collect();
+                    assert_eq!(InputState::parse(&words).unwrap(), state, "{}", line);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_held() {
+        let line = InputState::new().write();
+        assert_eq!(line, "0 0 -");
+        let words: Vec<&str> = line.split_whitespace().collect();
+        assert_eq!(InputState::parse(&words).unwrap(), InputState::new());
+    }
+
+    #[test]
+    fn playback_reads_what_the_recorder_writes() {
+        let mut input = InputState::new();
+        input.mouse = Vector2i::new(320, 240);
+        input.shift = true;
+        input.arrows = Vector2i::new(-1, 0);
+        let actions = vec![InputAction::KeyPressed(Key::Space, false),
+                           InputAction::MousePressed(MouseButton::Right, 3, 4)];
+
+        let (dt0, dt1): (f32, f32) = (1. / 60., 0.016);
+
+        // the same lines `Recorder` writes
+        let mut source = String::from("seed 1234\nscenario res/scenarios/my scenario.scenario\n");
+        source.push_str(&format!("tick 0 {} {}\n", dt0, InputState::new().write()));
+        source.push_str(&format!("tick 1 {} {}\n", dt1, input.write()));
+        for action in actions.iter() {
+            source.push_str(&action.write());
+            source.push('\n');
+        }
+
+        let mut playback = Playback::parse(&source).unwrap();
+        assert_eq!(playback.seed, 1234);
+        assert_eq!(playback.scenario, "res/scenarios/my scenario.scenario");
+
+        let first = playback.next_frame().unwrap();
+        assert_eq!(first.dt, dt0);
+        assert_eq!(first.input, InputState::new());
+        assert!(first.actions.is_empty());
+
+        let second = playback.next_frame().unwrap();
+        assert_eq!(second.dt, dt1);
+        assert_eq!(second.input, input);
+        assert_eq!(second.actions, actions);
+
+        assert!(playback.next_frame().is_none());
+    }
+
+    #[test]
+    fn playback_errors_point_at_the_line() {
+        let err = Playback::parse("seed 1\nscenario a\nclosed\n").err().unwrap();
+        assert_eq!(err, "line 3: action before the first tick");
+        let err = Playback::parse("seed 1\nscenario a\ntick 0 fast 0 0 -\n").err().unwrap();
+        assert_eq!(err, "line 3: bad number fast");
+        assert!(Playback::parse("scenario a\n").is_err());
+    }
+}
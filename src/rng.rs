@@ -0,0 +1,36 @@
+extern crate rand;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+/// The one source of randomness for everything that changes the game. It's seeded, so the
+/// same seed and the same input play out the same game again.
+pub struct GameRng {
+    seed: u32,
+    rng: XorShiftRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u32) -> Self {
+        // xorshift can't start from all zeroes, the constants make sure it never does
+        let rng = XorShiftRng::from_seed([seed, seed ^ 0x9e37_79b9, 0x6c07_8965, 0x2545_f491]);
+        GameRng {
+            seed: seed,
+            rng: rng,
+        }
+    }
+
+    /// A fresh seed for games nobody asked to repeat.
+    pub fn random_seed() -> u32 {
+        rand::thread_rng().gen()
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+}
@@ -0,0 +1,526 @@
+use std::fs::File;
+use std::io::Read;
+
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use resource_manager::{FontManager, FontId, TextureManager};
+use wagon::{Wagon, TileType};
+use train::Train;
+use actor::ActorKind;
+use actor_manager::ActorManager;
+use cargo::ItemType;
+use station::StationManager;
+use enemy::EnemyKind;
+use waves::{DifficultyCurve, WaveClock};
+use game_consts::*;
+
+pub const DEFAULT_SCENARIO: &'static str = "res/scenarios/default.scenario";
+
+#[derive(Clone, Debug)]
+pub enum Objective {
+    /// Dock at the station with this name.
+    ReachStation(String),
+    /// Lose if fewer crew than this are left.
+    KeepCrew(usize),
+    /// Hold out this many seconds.
+    Survive(f32),
+}
+
+impl Objective {
+    /// Goals have to be completed to win, the others only make you lose.
+    fn is_goal(&self) -> bool {
+        match *self {
+            Objective::KeepCrew(_) => false,
+            _ => true,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match *self {
+            Objective::ReachStation(ref name) => format!("REACH {}", name),
+            Objective::KeepCrew(n) => format!("KEEP {} CREW ALIVE", n),
+            Objective::Survive(t) => format!("SURVIVE {}:{:02}", t as i32 / 60, t as i32 % 60),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ObjectiveState {
+    Pending,
+    Complete,
+    Failed,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+    Won,
+    Lost,
+}
+
+/// Everything a scenario file describes. The format is one `key = value` per line,
+/// `#` starts a comment, and keys like `wagon` or `objective` can repeat.
+#[derive(Clone)]
+pub struct Scenario {
+    pub name: String,
+    pub script: Option<String>,
+    /// Random events that can happen along the way.
+    pub events: Option<String>,
+    pub top_speed: f32,
+    pub accel: f32,
+    pub money: i32,
+    /// Coal in the firebox at the start.
+    pub stoke: f32,
+    /// Width and height of each wagon, locomotive first.
+    pub wagons: Vec<(u32, u32)>,
+    /// `(wagon, row, column, type)`
+    pub furniture: Vec<(usize, usize, usize, TileType)>,
+    /// `(wagon, row, column, capacity, contents)`
+    pub storages: Vec<(usize, usize, usize, u32, Vec<(ItemType, u32)>)>,
+    /// `(kind, wagon, row, column)`
+    pub crew: Vec<(ActorKind, usize, usize, usize)>,
+    /// Seconds into the game and where, relative to the train, each enemy shows up.
+    pub enemies: Vec<(f32, Vector2f)>,
+    /// Waves of enemies on top of the fixed ones, none without a `wave_clock`.
+    pub waves: Option<DifficultyCurve>,
+    pub objectives: Vec<Objective>,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Scenario, String> {
+        let mut source = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut source))
+            .map_err(|e| format!("can't read {}: {}", path, e))?;
+        Scenario::parse(&source).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(source: &str) -> Result<Scenario, String> {
+        let mut s = Scenario {
+            name: String::from("UNNAMED"),
+            script: None,
+            events: None,
+            top_speed: 1000.,
+            accel: 1800.,
+            money: 100,
+            stoke: 10.,
+            wagons: vec![],
+            furniture: vec![],
+            storages: vec![],
+            crew: vec![],
+            enemies: vec![],
+            waves: None,
+            objectives: vec![],
+        };
+
+        for (n, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let value = match parts.next() {
+                Some(value) => value.trim(),
+                None => return Err(format!("line {}: expected key = value", n + 1)),
+            };
+            let words: Vec<&str> = value.split_whitespace().collect();
+
+            s.parse_entry(key, value, &words).map_err(|e| format!("line {}: {}", n + 1, e))?;
+        }
+
+        if s.wagons.len() < 2 {
+            return Err(String::from("a scenario needs a locomotive and at least one wagon"));
+        }
+        if s.objectives.iter().all(|o| !o.is_goal()) {
+            return Err(String::from("a scenario needs something to win"));
+        }
+        if let Some(ref mut waves) = s.waves {
+            waves.points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        }
+        Ok(s)
+    }
+
+    fn parse_entry(&mut self, key: &str, value: &str, words: &[&str]) -> Result<(), String> {
+        match key {
+            "name" => self.name = value.to_uppercase(),
+            "script" => self.script = Some(String::from(value)),
+            "events" => self.events = Some(String::from(value)),
+            "top_speed" => self.top_speed = num(words, 0)?,
+            "accel" => self.accel = num(words, 0)?,
+            "money" => self.money = num(words, 0)?,
+            "stoke" => self.stoke = num(words, 0)?,
+            "wagon" => self.wagons.push((num(words, 0)?, num(words, 1)?)),
+            "furniture" => {
+                let tile_type = match word(words, 3)?.to_uppercase().as_str() {
+                    "BED" => TileType::Bed,
+                    "GALLEY" => TileType::Galley,
+                    "FIREBOX" => TileType::Firebox,
+                    other => return Err(format!("unknown furniture {}", other)),
+                };
+                self.furniture.push((num(words, 0)?, num(words, 1)?, num(words, 2)?, tile_type));
+            }
+            "storage" => {
+                // the contents come in item amount pairs after the capacity
+                let mut contents = vec![];
+                let mut k = 4;
+                while k < words.len() {
+                    contents.push((item(word(words, k)?)?, num(words, k + 1)?));
+                    k += 2;
+                }
+                self.storages.push((num(words, 0)?, num(words, 1)?, num(words, 2)?, num(words, 3)?, contents));
+            }
+            "crew" => {
+                let kind = match word(words, 0)?.to_uppercase().as_str() {
+                    "CREW" => ActorKind::Crew,
+                    "ENGINEER" => ActorKind::Engineer,
+                    other => return Err(format!("unknown crew kind {}", other)),
+                };
+                self.crew.push((kind, num(words, 1)?, num(words, 2)?, num(words, 3)?));
+            }
+            "enemy" => self.enemies.push((num(words, 0)?, Vector2f::new(num(words, 1)?, num(words, 2)?))),
+            "wave_clock" => {
+                let clock = match word(words, 0)? {
+                    "time" => WaveClock::Time,
+                    "distance" => WaveClock::Distance,
+                    other => return Err(format!("unknown wave clock {}", other)),
+                };
+                self.waves = Some(DifficultyCurve::new(clock, num(words, 1)?));
+            }
+            "wave_size" | "wave_point" | "wave_kind" => {
+                let waves = match self.waves.as_mut() {
+                    Some(waves) => waves,
+                    None => return Err(format!("{} before wave_clock", key)),
+                };
+                match key {
+                    "wave_size" => {
+                        waves.base_count = num(words, 0)?;
+                        waves.count_per_level = num(words, 1)?;
+                    }
+                    "wave_point" => waves.points.push((num(words, 0)?, num(words, 1)?)),
+                    _ => {
+                        let name = word(words, 0)?;
+                        let kind = EnemyKind::from_name(name).ok_or(format!("unknown enemy kind {}", name))?;
+                        waves.kinds.push((kind, num(words, 1)?, num(words, 2)?));
+                    }
+                }
+            }
+            "objective" => {
+                let objective = match word(words, 0)? {
+                    "reach_station" => Objective::ReachStation(word(words, 1)?.to_uppercase()),
+                    "keep_crew" => Objective::KeepCrew(num(words, 1)?),
+                    "survive" => Objective::Survive(num(words, 1)?),
+                    other => return Err(format!("unknown objective {}", other)),
+                };
+                self.objectives.push(objective);
+            }
+            other => return Err(format!("unknown key {}", other)),
+        }
+        Ok(())
+    }
+
+    /// Builds the train, locomotive first, and fills it with furniture and cargo.
+    pub fn build_train<'a>(&self, train: &mut Train<'a>, tm: &'a TextureManager) -> Result<(), String> {
+        train.init(self.top_speed, self.accel);
+        for &(w, h) in self.wagons.iter() {
+            if h % 2 == 0 {
+                return Err(format!("wagon height {} needs to be odd", h));
+            }
+            let mut wagon = Wagon::new(tm, w, h);
+            if let Some(last) = train.wagons.last_mut() {
+                last.connect(&mut wagon, tm);
+            }
+            train.wagons.push(wagon);
+        }
+
+        for &(w, i, j, ref tile_type) in self.furniture.iter() {
+            check_floor(train, w, i, j)?;
+            train.wagons[w].place_furniture(i, j, tile_type.clone());
+        }
+        for &(w, i, j, capacity, ref contents) in self.storages.iter() {
+            check_floor(train, w, i, j)?;
+            let storage = train.wagons[w].place_storage(i, j, capacity);
+            for &(item, n) in contents.iter() {
+                storage.add(item, n);
+            }
+        }
+        train.boiler.stoke(self.stoke);
+
+        train.set_position2f(0., 0.);
+        train.rebuild_pfgrids();
+        Ok(())
+    }
+
+    pub fn spawn_crew<'a>(&self, am: &mut ActorManager<'a>, train: &Train, tm: &'a TextureManager) -> Result<(), String> {
+        for &(kind, w, i, j) in self.crew.iter() {
+            check_floor(train, w, i, j)?;
+            am.spawn_actor(tm, kind, train.wagons[w].tiles[i][j].sprite.get_position());
+        }
+        Ok(())
+    }
+}
+
+fn word<'s>(words: &[&'s str], n: usize) -> Result<&'s str, String> {
+    words.get(n).cloned().ok_or(format!("missing value {}", n + 1))
+}
+
+fn num<T: ::std::str::FromStr>(words: &[&str], n: usize) -> Result<T, String> {
+    let w = word(words, n)?;
+    // floats take nan and inf too, nothing in a scenario makes sense with those
+    if w.parse::<f64>().map(|x| !x.is_finite()).unwrap_or(false) {
+        return Err(format!("bad number {}", w));
+    }
+    w.parse().map_err(|_| format!("bad number {}", w))
+}
+
+fn item(name: &str) -> Result<ItemType, String> {
+    ItemType::all()
+        .iter()
+        .cloned()
+        .find(|i| i.name() == name.to_uppercase())
+        .ok_or(format!("unknown item {}", name))
+}
+
+fn check_floor(train: &Train, w: usize, i: usize, j: usize) -> Result<(), String> {
+    match train.wagons.get(w).and_then(|wagon| wagon.tiles.get(i)).and_then(|row| row.get(j)) {
+        Some(tile) if !tile.is_solid => Ok(()),
+        Some(_) => Err(format!("tile {} {},{} isn't floor", w, i, j)),
+        None => Err(format!("no tile {} {},{}", w, i, j)),
+    }
+}
+
+/// Tracks a running scenario: releases its enemies on schedule and decides when it's won or lost.
+pub struct Mission<'a> {
+    pub scenario: Scenario,
+    pub objectives: Vec<(Objective, ObjectiveState)>,
+    pub outcome: Option<Outcome>,
+    next_enemy: usize,
+    text: Text<'a>,
+}
+
+impl<'a> Mission<'a> {
+    pub fn new(scenario: Scenario) -> Self {
+        let mut enemies = scenario.enemies.clone();
+        // the schedule is released in order
+        enemies.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let objectives = scenario.objectives.iter().map(|o| (o.clone(), ObjectiveState::Pending)).collect();
+
+        Mission {
+            scenario: Scenario { enemies: enemies, ..scenario },
+            objectives: objectives,
+            outcome: None,
+            next_enemy: 0,
+            text: Text::new().unwrap(),
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager) {
+        self.text.set_font(fm.get(FontId::Joystix));
+        self.text.set_character_size(14);
+        self.text.set_position2f(20., 620.);
+    }
+
+    /// Enemies due by game time `time`, already placed relative to `train_pos`.
+    pub fn due_enemies(&mut self, time: f32, train_pos: Vector2f) -> Vec<Vector2f> {
+        let mut due = vec![];
+        while let Some(&(at, offset)) = self.scenario.enemies.get(self.next_enemy) {
+            if at > time {
+                break;
+            }
+            due.push(train_pos + offset);
+            self.next_enemy += 1;
+        }
+        due
+    }
+
+    pub fn update(&mut self, time: f32, crew: usize, stations: &StationManager) -> Option<Outcome> {
+        if self.outcome.is_some() {
+            return self.outcome;
+        }
+
+        let docked = stations.docked_station().map(|s| s.name.clone());
+        for &mut (ref objective, ref mut state) in self.objectives.iter_mut() {
+            *state = match (objective, *state) {
+                (_, ObjectiveState::Complete) => ObjectiveState::Complete,
+                (&Objective::ReachStation(ref name), _) if docked.as_ref() == Some(name) => ObjectiveState::Complete,
+                (&Objective::Survive(t), _) if time >= t => ObjectiveState::Complete,
+                (&Objective::KeepCrew(n), _) if crew < n => ObjectiveState::Failed,
+                (_, state) => state,
+            };
+        }
+
+        if self.objectives.iter().any(|&(_, s)| s == ObjectiveState::Failed) {
+            self.outcome = Some(Outcome::Lost);
+        } else if self.objectives.iter().filter(|&&(ref o, _)| o.is_goal()).all(|&(_, s)| s == ObjectiveState::Complete) {
+            // the constraints held all the way
+            for &mut (_, ref mut state) in self.objectives.iter_mut() {
+                *state = ObjectiveState::Complete;
+            }
+            self.outcome = Some(Outcome::Won);
+        }
+
+        let mut s = format!("{}\n", self.scenario.name);
+        for &(ref o, state) in self.objectives.iter() {
+            s.push_str(&format!("{} {}\n", checkbox(state), o.describe()));
+        }
+        self.text.set_string(&s);
+
+        self.outcome
+    }
+
+    /// Draws the objective list, call with the ui view set.
+    pub fn draw(&self, window: &mut RenderWindow) {
+        window.draw(&self.text);
+    }
+}
+
+fn checkbox(state: ObjectiveState) -> &'static str {
+    match state {
+        ObjectiveState::Pending => "[ ]",
+        ObjectiveState::Complete => "[X]",
+        ObjectiveState::Failed => "[!]",
+    }
+}
+
+/// What's shown once a scenario is over.
+pub struct ResultsScreen<'a> {
+    title: Text<'a>,
+    body: Text<'a>,
+}
+
+impl<'a> ResultsScreen<'a> {
+    pub fn new() -> Self {
+        ResultsScreen {
+            title: Text::new().unwrap(),
+            body: Text::new().unwrap(),
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager) {
+        self.title.set_font(fm.get(FontId::Joystix));
+        self.title.set_character_size(60);
+        self.title.set_position2f(500., 150.);
+        self.body.set_font(fm.get(FontId::Joystix));
+        self.body.set_character_size(20);
+        self.body.set_position2f(500., 280.);
+    }
+
+    pub fn show(&mut self, mission: &Mission, time: f32, distance: f32, money: i32) {
+        match mission.outcome {
+            Some(Outcome::Won) => {
+                self.title.set_string("VICTORY");
+                self.title.set_color(&Color::green());
+            }
+            _ => {
+                self.title.set_string("DEFEAT");
+                self.title.set_color(&Color::red());
+            }
+        }
+
+        let mut s = format!("{}\n\n", mission.scenario.name);
+        for &(ref o, state) in mission.objectives.iter() {
+            s.push_str(&format!("{} {}\n", checkbox(state), o.describe()));
+        }
+        s.push_str(&format!("\nTIME {}:{:02}\nDISTANCE {}M\nMONEY ${}\n\nR: PLAY AGAIN   ESC: QUIT",
+                            time as i32 / 60,
+                            time as i32 % 60,
+                            (distance / TILE_SIZE_X as f32) as i32,
+                            money));
+        self.body.set_string(&s);
+    }
+
+    /// Call with the ui view set.
+    pub fn draw(&self, window: &mut RenderWindow) {
+        window.draw(&self.title);
+        window.draw(&self.body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: &'static str = "name = Test Run   # trailing comments are fine
+money = 50
+
+wagon = 2 3
+wagon = 4 3
+furniture = 1 1 2 bed
+storage = 1 2 2 30 coal 10 food 5
+crew = engineer 1 1 1
+enemy = 5 100 -50
+
+wave_clock = time 30
+wave_size = 3 0.5
+wave_point = 200 4
+wave_point = 0 1
+wave_kind = runner 1 2
+
+objective = survive 120
+objective = keep_crew 1
+";
+
+    fn error(source: &str) -> String {
+        Scenario::parse(source).err().unwrap()
+    }
+
+    #[test]
+    fn parses_every_key() {
+        let s = Scenario::parse(SMALL).unwrap();
+        assert_eq!(s.name, "TEST RUN");
+        assert_eq!(s.money, 50);
+        assert_eq!(s.wagons, vec![(2, 3), (4, 3)]);
+        assert!(s.furniture == vec![(1, 1, 2, TileType::Bed)]);
+        assert_eq!(s.storages.len(), 1);
+        assert_eq!(s.storages[0].3, 30);
+        assert_eq!(s.storages[0].4, vec![(ItemType::Coal, 10), (ItemType::Food, 5)]);
+        assert_eq!(s.crew, vec![(ActorKind::Engineer, 1, 1, 1)]);
+        assert!(s.enemies == vec![(5., Vector2f::new(100., -50.))]);
+        assert_eq!(s.objectives.len(), 2);
+
+        let waves = s.waves.unwrap();
+        assert_eq!(waves.clock, WaveClock::Time);
+        assert_eq!(waves.interval, 30.);
+        assert_eq!((waves.base_count, waves.count_per_level), (3., 0.5));
+        // sorted by progress whatever order they came in
+        assert_eq!(waves.points, vec![(0., 1.), (200., 4.)]);
+        assert_eq!(waves.kinds, vec![(EnemyKind::Runner, 1., 2.)]);
+    }
+
+    #[test]
+    fn shipped_scenarios_parse() {
+        Scenario::parse(include_str!("../res/scenarios/default.scenario")).unwrap();
+        Scenario::parse(include_str!("../res/scenarios/holdout.scenario")).unwrap();
+    }
+
+    #[test]
+    fn bad_lines_name_their_line() {
+        assert_eq!(error("name = a\nwagon 2 3"), "line 2: expected key = value");
+        assert_eq!(error("\n\ncolour = red"), "line 3: unknown key colour");
+        assert_eq!(error("wagon = 2"), "line 1: missing value 2");
+        assert_eq!(error("money = lots"), "line 1: bad number lots");
+        assert_eq!(error("wagon = 2 3\nfurniture = 0 1 1 piano"), "line 2: unknown furniture PIANO");
+        assert_eq!(error("crew = cook 0 1 1"), "line 1: unknown crew kind COOK");
+        assert_eq!(error("storage = 0 1 1 10 gold 5"), "line 1: unknown item gold");
+        assert_eq!(error("wave_point = 0 1"), "line 1: wave_point before wave_clock");
+        assert_eq!(error("wave_clock = weekly 3"), "line 1: unknown wave clock weekly");
+        assert_eq!(error("wave_clock = time 3\nwave_kind = dragon 0 1"), "line 2: unknown enemy kind dragon");
+        assert_eq!(error("objective = win"), "line 1: unknown objective win");
+    }
+
+    #[test]
+    fn rejects_nan_and_inf() {
+        assert_eq!(error("enemy = nan 0 0"), "line 1: bad number nan");
+        assert_eq!(error("wave_clock = time 3\nwave_point = inf 1"), "line 2: bad number inf");
+        assert_eq!(error("top_speed = -inf"), "line 1: bad number -inf");
+    }
+
+    #[test]
+    fn needs_a_train_and_a_goal() {
+        assert_eq!(error("wagon = 2 3\nobjective = survive 10"),
+                   "a scenario needs a locomotive and at least one wagon");
+        assert_eq!(error("wagon = 2 3\nwagon = 4 3\nobjective = keep_crew 2"),
+                   "a scenario needs something to win");
+    }
+}
@@ -0,0 +1,210 @@
+extern crate rhai;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+
+use rhai::{Engine, Scope, EvalAltResult};
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use resource_manager::{FontManager, FontId};
+use train::Train;
+use enemy::Enemy;
+
+/// How long a script message stays on screen, in seconds.
+const MESSAGE_TIME: f32 = 4.;
+
+/// Something a script asked for. Scripts never touch the game directly, they queue these
+/// and the game checks and applies them after the hook returns.
+pub enum ScriptAction {
+    SpawnEnemy(Vector2f),
+    OpenDoor(usize, usize, usize),
+    Message(String),
+}
+
+/// What scripts can read about the game, refreshed before every hook.
+struct ScriptWorld {
+    speed: f32,
+    distance: f32,
+    train_pos: Vector2f,
+    enemies: usize,
+    flags: HashSet<String>,
+}
+
+pub struct Scripting<'a> {
+    engine: Engine,
+    scope: Scope,
+    world: Rc<RefCell<ScriptWorld>>,
+    actions: Rc<RefCell<Vec<ScriptAction>>>,
+    /// Hooks still worth calling. One goes once the engine says the script doesn't define it,
+    /// or once it fails.
+    hooks: HashSet<&'static str>,
+    message: Text<'a>,
+    message_timer: f32,
+}
+
+impl<'a> Scripting<'a> {
+    pub fn new() -> Self {
+        let world = Rc::new(RefCell::new(ScriptWorld {
+            speed: 0.,
+            distance: 0.,
+            train_pos: Vector2f::new(0., 0.),
+            enemies: 0,
+            flags: HashSet::new(),
+        }));
+        let actions = Rc::new(RefCell::new(vec![]));
+
+        let mut engine = Engine::new();
+        register_api(&mut engine, &world, &actions);
+
+        Scripting {
+            engine: engine,
+            scope: Scope::new(),
+            world: world,
+            actions: actions,
+            hooks: HashSet::new(),
+            message: Text::new().unwrap(),
+            message_timer: 0.,
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager) {
+        self.message.set_font(fm.get(FontId::Joystix));
+        self.message.set_character_size(22);
+        self.message.set_color(&Color::white());
+        self.message.set_position2f(400., 80.);
+    }
+
+    /// Runs the script at `path`, which defines the hooks. Anything it had defined before is kept,
+    /// so only load one script per game.
+    pub fn load(&mut self, path: &str) {
+        let mut source = String::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut source)) {
+            println!("can't read script {}: {}", path, e);
+            return;
+        }
+
+        if let Err(e) = self.engine.eval_with_scope::<()>(&mut self.scope, &source) {
+            println!("script {} failed: {:?}", path, e);
+            return;
+        }
+
+        // the first call finds out which of them the script actually defines
+        self.hooks = ["on_tick", "on_door_opened", "on_enemy_killed"].iter().cloned().collect();
+    }
+
+    fn sync(&mut self, train: &Train, enemies: &[Enemy]) {
+        let mut world = self.world.borrow_mut();
+        world.speed = train.signed_speed();
+        world.distance = train.distance;
+        world.train_pos = train.get_origin();
+        world.enemies = enemies.iter().filter(|e| !e.is_dead()).count();
+    }
+
+    /// Deals with what hook `hook` returned.
+    fn finish(&mut self, hook: &'static str, result: Result<(), EvalAltResult>) {
+        match result {
+            Ok(()) => {}
+            // the script doesn't have this hook, which is fine
+            Err(EvalAltResult::ErrorFunctionNotFound(ref name)) if name == hook => {
+                self.hooks.remove(&hook);
+            }
+            Err(e) => {
+                println!("{} failed: {:?}", hook, e);
+                // don't spam the same error every frame
+                self.hooks.remove(&hook);
+            }
+        }
+    }
+
+    pub fn on_tick(&mut self, dt: f32, train: &Train, enemies: &[Enemy]) {
+        self.sync(train, enemies);
+        if self.hooks.contains("on_tick") {
+            let result = self.engine.call_fn("on_tick", (&mut (dt as f64),));
+            self.finish("on_tick", result);
+        }
+
+        if self.message_timer > 0. {
+            self.message_timer -= dt;
+        }
+    }
+
+    pub fn on_door_opened(&mut self, (w, i, j): (usize, usize, usize), train: &Train, enemies: &[Enemy]) {
+        self.sync(train, enemies);
+        if self.hooks.contains("on_door_opened") {
+            let result = self.engine.call_fn("on_door_opened", (&mut (w as i64), &mut (i as i64), &mut (j as i64)));
+            self.finish("on_door_opened", result);
+        }
+    }
+
+    pub fn on_enemy_killed(&mut self, pos: Vector2f, train: &Train, enemies: &[Enemy]) {
+        self.sync(train, enemies);
+        if self.hooks.contains("on_enemy_killed") {
+            let result = self.engine.call_fn("on_enemy_killed", (&mut (pos.x as f64), &mut (pos.y as f64)));
+            self.finish("on_enemy_killed", result);
+        }
+    }
+
+    /// Everything the scripts asked for since the last call. Messages are handled here.
+    pub fn take_actions(&mut self) -> Vec<ScriptAction> {
+        let actions: Vec<ScriptAction> = self.actions.borrow_mut().drain(..).collect();
+        let mut rest = vec![];
+        for action in actions {
+            match action {
+                ScriptAction::Message(s) => {
+                    self.message.set_string(&s);
+                    self.message_timer = MESSAGE_TIME;
+                }
+                other => rest.push(other),
+            }
+        }
+        rest
+    }
+
+    /// Draws the latest script message, call with the ui view set.
+    pub fn draw(&self, window: &mut RenderWindow) {
+        if self.message_timer > 0. {
+            window.draw(&self.message);
+        }
+    }
+}
+
+/// The functions scripts can call. They only read the `ScriptWorld` snapshot and queue actions.
+fn register_api(engine: &mut Engine, world: &Rc<RefCell<ScriptWorld>>, actions: &Rc<RefCell<Vec<ScriptAction>>>) {
+    let w = world.clone();
+    engine.register_fn("train_speed", move || w.borrow().speed as f64);
+    let w = world.clone();
+    engine.register_fn("train_distance", move || w.borrow().distance as f64);
+    let w = world.clone();
+    engine.register_fn("train_x", move || w.borrow().train_pos.x as f64);
+    let w = world.clone();
+    engine.register_fn("train_y", move || w.borrow().train_pos.y as f64);
+    let w = world.clone();
+    engine.register_fn("enemy_count", move || w.borrow().enemies as i64);
+
+    // flags let scripts remember things between hooks, e.g. that a warning was shown already
+    let w = world.clone();
+    engine.register_fn("set_flag", move |name: String| {
+        w.borrow_mut().flags.insert(name);
+    });
+    let w = world.clone();
+    engine.register_fn("has_flag", move |name: String| w.borrow().flags.contains(&name));
+
+    let a = actions.clone();
+    engine.register_fn("spawn_enemy", move |x: f64, y: f64| {
+        a.borrow_mut().push(ScriptAction::SpawnEnemy(Vector2f::new(x as f32, y as f32)));
+    });
+    let a = actions.clone();
+    engine.register_fn("open_door", move |w: i64, i: i64, j: i64| {
+        if w >= 0 && i >= 0 && j >= 0 {
+            a.borrow_mut().push(ScriptAction::OpenDoor(w as usize, i as usize, j as usize));
+        }
+    });
+    let a = actions.clone();
+    engine.register_fn("message", move |s: String| {
+        a.borrow_mut().push(ScriptAction::Message(s));
+    });
+}
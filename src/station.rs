@@ -0,0 +1,165 @@
+extern crate sfml;
+extern crate rand;
+
+use rand::Rng;
+use sfml::graphics::*;
+use sfml::system::Vector2f;
+
+use resource_manager::{FontManager, FontId};
+use wagon::Direction;
+use world::World;
+use train::Train;
+use rng::GameRng;
+use game_consts::*;
+
+const STATION_NAMES: [&'static str; 8] = ["ASHFORD", "BRIGHTWATER", "COLDHARBOUR", "DUNMORE",
+                                          "ELMSTEAD", "FAIRLIGHT", "GREYSTONE", "HOLLOWAY"];
+
+/// How far ahead of the train stations get spawned.
+const SPAWN_AHEAD: f32 = 20000.;
+
+pub struct Station<'a> {
+    pub name: String,
+    /// Track distance at which the platform starts.
+    pub position: f32,
+    pub length: f32,
+    pub side: Direction,
+    /// Coal the station can still sell.
+    pub coal: u32,
+    platform: RectangleShape<'a>,
+    label: Text<'a>,
+}
+
+impl<'a> Station<'a> {
+    pub fn new(font: &'a Font, name: &str, position: f32, length: f32, side: Direction) -> Self {
+        let mut platform = RectangleShape::new().unwrap();
+        platform.set_size2f(length, 2. * TILE_SIZE_Y as f32);
+        platform.set_fill_color(&Color::new_rgb(150, 150, 160));
+        platform.set_outline_color(&Color::new_rgb(80, 80, 80));
+        platform.set_outline_thickness(4.);
+
+        let mut label = Text::new().unwrap();
+        label.set_font(font);
+        label.set_character_size(40);
+        label.set_color(&Color::black());
+        label.set_string(name);
+
+        Station {
+            name: name.to_string(),
+            position: position,
+            length: length,
+            side: side,
+            coal: 100,
+            platform: platform,
+            label: label,
+        }
+    }
+
+    /// Where the platform starts on screen given where the front of the train is.
+    pub fn screen_x(&self, train: &Train) -> f32 {
+        train.front_x() + self.position - train.distance
+    }
+
+    /// The whole train has to be stopped inside the platform zone.
+    pub fn can_dock(&self, train: &Train) -> bool {
+        let start = self.screen_x(train);
+        train.current_speed <= 0. && train.rear_x() >= start && train.front_x() <= start + self.length
+    }
+
+    /// How far the front of the train went past the end of the platform, if it did.
+    pub fn overshot_by(&self, train: &Train) -> Option<f32> {
+        let past = train.front_x() - (self.screen_x(train) + self.length);
+        if past > 0. && train.rear_x() < self.screen_x(train) + self.length {
+            Some(past)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct StationManager<'a> {
+    pub stations: Vec<Station<'a>>,
+    pub docked: Option<usize>,
+    font: Option<&'a Font>,
+    next_name: usize,
+}
+
+impl<'a> StationManager<'a> {
+    pub fn new() -> Self {
+        StationManager {
+            stations: vec![],
+            docked: None,
+            font: None,
+            next_name: 0,
+        }
+    }
+
+    pub fn init(&mut self, fm: &'a FontManager) {
+        self.font = Some(fm.get(FontId::Joystix));
+    }
+
+    fn spawn(&mut self, position: f32, rng: &mut GameRng) {
+        let length = rng.gen_range(2000, 3000) as f32;
+        let side = if rng.gen() {
+            Direction::North
+        } else {
+            Direction::South
+        };
+        let name = STATION_NAMES[self.next_name % STATION_NAMES.len()];
+        self.next_name += 1;
+
+        self.stations.push(Station::new(self.font.unwrap(), name, position, length, side));
+    }
+
+    pub fn update(&mut self, train: &Train, world: &World, rng: &mut GameRng) {
+        // keep stations spawned ahead of the train
+        let last = self.stations.last().map(|s| s.position + s.length).unwrap_or(3000.);
+        if self.stations.is_empty() || last < train.distance + SPAWN_AHEAD {
+            let gap = rng.gen_range(6000, 12000) as f32;
+            let position = if self.stations.is_empty() { last } else { last + gap };
+            self.spawn(position, rng);
+        }
+
+        self.docked = self.stations.iter().position(|s| s.can_dock(train));
+
+        // move the platforms along the track next to the train
+        for s in self.stations.iter_mut() {
+            let x = s.screen_x(train);
+            // same offset the wagons use to sit on the rails
+            let center_y = match world.track_y_at(x) {
+                Some(y) => y + 1.5 * TILE_SIZE_Y as f32,
+                None => train.get_origin().y,
+            };
+            let half_height = train.total_size.y as f32 * TILE_SIZE_Y as f32 / 2.;
+            let y = match s.side {
+                Direction::North => center_y - half_height - 2. * TILE_SIZE_Y as f32 - 16.,
+                _ => center_y + half_height + 16.,
+            };
+            s.platform.set_position2f(x, y);
+            s.label.set_position2f(x + 40., y + 40.);
+        }
+    }
+
+    pub fn docked_station(&self) -> Option<&Station<'a>> {
+        self.docked.map(|idx| &self.stations[idx])
+    }
+
+    pub fn docked_station_mut(&mut self) -> Option<&mut Station<'a>> {
+        match self.docked {
+            Some(idx) => Some(&mut self.stations[idx]),
+            None => None,
+        }
+    }
+
+    /// The next station the train hasn't fully passed yet.
+    pub fn next_station(&self, train: &Train) -> Option<&Station<'a>> {
+        self.stations.iter().find(|s| s.position + s.length > train.distance)
+    }
+
+    pub fn draw(&self, window: &mut RenderWindow, states: &mut RenderStates) {
+        for s in self.stations.iter() {
+            window.draw_with_renderstates(&s.platform, states);
+            window.draw_with_renderstates(&s.label, states);
+        }
+    }
+}
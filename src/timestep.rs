@@ -0,0 +1,20 @@
+use sfml::graphics::{RenderStates, Transform};
+use sfml::system::Vector2f;
+
+/// Length of one simulation step in game seconds. The game always advances in steps
+/// of this size, however fast or slow frames come.
+pub const SIM_STEP: f32 = 1. / 60.;
+
+/// Most steps run for a single frame. A frame that needs more than this drops the rest
+/// and the game slows down for a moment instead of falling further and further behind.
+pub const MAX_STEPS: u32 = 16;
+
+/// Render states that draw things `offset` away from where they are, so they can be
+/// shown somewhere between two simulation steps.
+pub fn offset_states<'s>(offset: Vector2f) -> RenderStates<'s> {
+    let mut transform = Transform::new_identity();
+    transform.translate(offset.x, offset.y);
+    let mut states = RenderStates::default();
+    states.transform = transform;
+    states
+}
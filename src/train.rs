@@ -3,6 +3,9 @@ use sfml::graphics::*;
 
 use pathfinding::*;
 use wagon::*;
+use boiler::Boiler;
+use resource_manager::{TextureManager, TextureId};
+use console::{CommandRegistry, CommandContext, arg};
 
 use game_consts::*;
 
@@ -11,11 +14,16 @@ pub struct Train<'a> {
     pub moving: bool,
     pub current_speed: f32,
     pub top_speed: f32,
+    /// Speed gained per second at full pressure, braking takes off a tenth of it.
     pub accel: f32,
     pub pfgrid_in: PathfindingGrid,
     pub pfgrid_out: PathfindingGrid,
     pub pfgrid_all: PathfindingGrid,
     pub total_size: Vector2u,
+    pub boiler: Boiler,
+    /// Odometer, how far along the track the front of the train is.
+    pub distance: f32,
+    pub reversing: bool,
 }
 
 impl<'a> Train<'a> {
@@ -30,6 +38,9 @@ impl<'a> Train<'a> {
             pfgrid_out: PathfindingGrid::new(),
             pfgrid_all: PathfindingGrid::new(),
             total_size: Vector2u::new(0, 0),
+            boiler: Boiler::new(),
+            distance: 0.,
+            reversing: false,
         }
     }
 
@@ -38,14 +49,96 @@ impl<'a> Train<'a> {
         self.accel = accel;
     }
 
-    pub fn update(&mut self) {
-        if self.moving {
-            if self.current_speed + self.accel < self.top_speed {
-                self.current_speed += self.accel;
-            }
+    pub fn update(&mut self, dt: f32) {
+        self.boiler.update(dt, self.moving);
+
+        // the boiler pressure decides how fast we can go and how hard we can pull
+        let effort = self.boiler.tractive_effort();
+        let available_speed = self.top_speed * effort;
+
+        if self.moving && self.current_speed < available_speed {
+            self.current_speed = (self.current_speed + self.accel * effort * dt).min(available_speed);
         } else if self.current_speed > 0. {
-            self.current_speed -= self.accel / 10.;
+            // coast down when braking or when running out of steam
+            self.current_speed = (self.current_speed - self.accel / 10. * dt).max(0.);
+        }
+
+        self.distance += self.signed_speed() * dt;
+    }
+
+    /// Current speed along the track, negative when backing up.
+    pub fn signed_speed(&self) -> f32 {
+        if self.reversing {
+            -self.current_speed
+        } else {
+            self.current_speed
+        }
+    }
+
+    /// Switches direction, only possible while standing still.
+    pub fn toggle_reverse(&mut self) -> bool {
+        if self.current_speed > 0. {
+            return false;
+        }
+        self.reversing = !self.reversing;
+        true
+    }
+
+    /// Screen x of the front of the locomotive.
+    pub fn front_x(&self) -> f32 {
+        self.wagons[0]
+            .tiles
+            .iter()
+            .flat_map(|t| t.iter())
+            .map(|t| t.sprite.get_position().x)
+            .fold(::std::f32::MIN, f32::max) + TILE_SIZE_X as f32 / 2.
+    }
+
+    /// Screen x of the back of the last wagon.
+    pub fn rear_x(&self) -> f32 {
+        self.wagons
+            .last()
+            .unwrap()
+            .tiles
+            .iter()
+            .flat_map(|t| t.iter())
+            .map(|t| t.sprite.get_position().x)
+            .fold(::std::f32::MAX, f32::min) - TILE_SIZE_X as f32 / 2.
+    }
+
+    /// Opens or closes the door at `(wagon, row, column)`, doors only work while standing still.
+    /// `platform` is the side of the station the train is docked at, doors only open onto it.
+    pub fn toggle_door(&mut self,
+                       (w, i, j): (usize, usize, usize),
+                       platform: Option<&Direction>,
+                       tm: &'a TextureManager)
+                       -> bool {
+        if self.current_speed > 0. {
+            return false;
         }
+
+        {
+            let tile = &mut self.wagons[w].tiles[i][j];
+            if let TileType::Door(ref side) = tile.tile_type {
+                // closing is always fine, nobody should get stuck with a door open
+                if tile.is_solid && platform != Some(side) {
+                    return false;
+                }
+                tile.is_solid = !tile.is_solid;
+                let texture = if tile.is_solid {
+                    TextureId::DoorClosed
+                } else {
+                    TextureId::DoorOpen
+                };
+                tile.set_texture(tm.get(texture));
+            } else {
+                return false;
+            }
+        }
+        self.wagons[w].mark_dirty();
+
+        self.rebuild_pfgrids();
+        true
     }
 
     pub fn set_position2f(&mut self, x: f32, y: f32) {
@@ -127,6 +220,32 @@ impl<'a> Train<'a> {
         self.total_size.y = max_height as u32;
     }
 
+    /// Returns `(wagon, row, column)` of the non-solid tile containing `pos`, if any.
+    pub fn tile_index_at(&self, pos: Vector2f) -> Option<(usize, usize, usize)> {
+        for (w, wagon) in self.wagons.iter().enumerate() {
+            if let Some((i, j)) = wagon.tile_index_at(pos) {
+                if !wagon.tiles[i][j].is_solid {
+                    return Some((w, i, j));
+                }
+            }
+        }
+        None
+    }
+
+    /// Like `tile_index_at` but also finds solid tiles such as walls and closed doors.
+    pub fn tile_index_at_any(&self, pos: Vector2f) -> Option<(usize, usize, usize)> {
+        for (w, wagon) in self.wagons.iter().enumerate() {
+            if let Some((i, j)) = wagon.tile_index_at(pos) {
+                return Some((w, i, j));
+            }
+        }
+        None
+    }
+
+    pub fn tile_at(&self, pos: Vector2f) -> Option<&Tile<'a>> {
+        self.tile_index_at(pos).map(|(w, i, j)| &self.wagons[w].tiles[i][j])
+    }
+
     pub fn get_origin(&self) -> Vector2f {
         let first_wagon_height = self.wagons.last().unwrap().tiles.len();
         let first_tile_pos = self.wagons.last().unwrap().get_origin();
@@ -135,3 +254,38 @@ impl<'a> Train<'a> {
         train_pos
     }
 }
+
+pub fn register_commands(registry: &mut CommandRegistry) {
+    registry.register("speed", "speed <n>: set the train speed", cmd_speed);
+    registry.register("door", "open or close the door under the cursor", cmd_door);
+    registry.register("rebuild", "rebuild the pathfinding grids", cmd_rebuild);
+}
+
+fn cmd_speed(ctx: &mut CommandContext, args: &[&str]) -> Result<String, String> {
+    let speed: f32 = arg(args, 0, "speed")?;
+    let train = &mut *ctx.train;
+    train.current_speed = speed.max(0.).min(train.top_speed);
+    train.moving = train.current_speed > 0.;
+    Ok(format!("speed set to {}", train.current_speed))
+}
+
+fn cmd_door(ctx: &mut CommandContext, _: &[&str]) -> Result<String, String> {
+    let idx = ctx.train.tile_index_at_any(ctx.cursor).ok_or("no tile under the cursor")?;
+    let platform = ctx.stations.docked_station().map(|s| &s.side);
+    if ctx.train.toggle_door(idx, platform, ctx.tm) {
+        if ctx.train.wagons[idx.0].tiles[idx.1][idx.2].is_solid {
+            Ok(String::from("door closed"))
+        } else {
+            // same as opening it by clicking
+            ctx.scripting.on_door_opened(idx, ctx.train, ctx.enemies);
+            Ok(String::from("door opened"))
+        }
+    } else {
+        Err(String::from("not a door, the train is moving or there's no platform on that side"))
+    }
+}
+
+fn cmd_rebuild(ctx: &mut CommandContext, _: &[&str]) -> Result<String, String> {
+    ctx.train.rebuild_pfgrids();
+    Ok(String::from("pathfinding grids rebuilt"))
+}
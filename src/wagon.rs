@@ -11,7 +11,10 @@ use astar::*;
 
 use std::vec::IntoIter;
 use std::collections::VecDeque;
+use std::ops::Range;
+use std::cell::{Cell, RefCell};
 use pathfinding::{PathfindingGrid, PathfindingTile};
+use cargo::Storage;
 
 pub fn formula_rot(point: &Vector2f, angle_rad: f32) -> Vector2f {
     let mut new = Vector2f::new(0., 0.);
@@ -33,29 +36,79 @@ pub enum TileType {
     WallAndFloor,
     Door(Direction),
     Window(Direction),
+    Bed,
+    Galley,
+    Storage,
+    Firebox,
+}
+
+impl TileType {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TileType::WallAndFloor => "FLOOR",
+            TileType::Door(_) => "DOOR",
+            TileType::Window(_) => "WINDOW",
+            TileType::Bed => "BED",
+            TileType::Galley => "GALLEY",
+            TileType::Storage => "STORAGE",
+            TileType::Firebox => "FIREBOX",
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Tile<'a> {
     pub sprite: Sprite<'a>,
+    /// What the sprite is drawn with, go through `set_texture` so this stays in sync.
+    pub texture: Option<&'a Texture>,
     pub is_solid: bool,
     pub bounds: [Option<FloatRect>; 2],
     pub tile_type: TileType,
+    pub storage: Option<Storage>,
 }
 
 impl<'a> Tile<'a> {
     pub fn new() -> Self {
         Tile {
             sprite: Sprite::new().unwrap(),
+            texture: None,
             is_solid: false,
             bounds: [None; 2],
             tile_type: TileType::WallAndFloor,
+            storage: None,
+        }
+    }
+
+    /// What the player sees this tile as, walls and corners share `WallAndFloor` with the floor.
+    pub fn name(&self) -> &'static str {
+        match self.tile_type {
+            TileType::WallAndFloor if self.is_solid => "WALL",
+            ref t => t.name(),
         }
     }
 
+    /// True if `pos` lies on this tile, taking its rotation into account.
+    pub fn contains(&self, pos: Vector2f) -> bool {
+        let relative = pos - self.sprite.get_position();
+        let local = formula_rot(&relative, -self.sprite.get_rotation().to_radians()) + self.sprite.get_origin();
+        self.sprite.get_local_bounds().contains(local)
+    }
+
+    /// Bounding box `b` (one of `bounds`) where collisions check it in the world: offset from
+    /// the tile's position, not turned with the tile.
+    pub fn collision_box(&self, b: &FloatRect) -> FloatRect {
+        let pos = self.sprite.get_position();
+        FloatRect::new(b.left + pos.x, b.top + pos.y, b.width, b.height)
+    }
+
+    pub fn set_texture(&mut self, texture: &'a Texture) {
+        self.sprite.set_texture(texture, true);
+        self.texture = Some(texture);
+    }
+
     pub fn new_with_texture(texture: &'a Texture) -> Self {
         let mut new_tile = Tile::new();
-        new_tile.sprite.set_texture(texture, false);
+        new_tile.set_texture(texture);
         new_tile.sprite.set_origin2f(TILE_SIZE_X as f32 / 2., TILE_SIZE_Y as f32 / 2.);
         new_tile
     }
@@ -67,6 +120,11 @@ pub struct Wagon<'a> {
     pub connected_to: [Option<&'a mut Wagon<'a>>; 2],
     pub rotation: f32,
     pub center: Vector2f,
+    /// Tile quads laid out in the wagon's own grid, one vertex array per texture, so the whole
+    /// wagon goes out in a few draw calls. Moving or turning the wagon doesn't touch them.
+    batches: RefCell<Vec<(&'a Texture, VertexArray)>>,
+    /// Set when a tile's texture, color or rotation changed and `batches` needs building again.
+    dirty: Cell<bool>,
 }
 
 
@@ -87,45 +145,45 @@ impl<'a> Wagon<'a> {
                 tile.is_solid = true;
 
                 if (i, j) == (0, 0) {
-                    tile.sprite.set_texture(tex_man.get(TextureId::Corner), true);
+                    tile.set_texture(tex_man.get(TextureId::Corner));
                     tile.bounds[0] = Some(FloatRect::new(58., 58., 6., 6.));
                 } else if (i, j) == (size_y + 1, 0) {
-                    tile.sprite.set_texture(tex_man.get(TextureId::Corner), true); // bleft
+                    tile.set_texture(tex_man.get(TextureId::Corner)); // bleft
                     tile.sprite.set_rotation(270.);
                     tile.bounds[0] = Some(FloatRect::new(58., 0., 6., 6.));
                 } else if (i, j) == (0, size_x + 1) {
-                    tile.sprite.set_texture(tex_man.get(TextureId::Corner), true); // topright
+                    tile.set_texture(tex_man.get(TextureId::Corner)); // topright
                     tile.sprite.set_rotation(90.);
                     tile.bounds[0] = Some(FloatRect::new(0., 58., 6., 6.));
                 } else if (i, j) == (size_y + 1, size_x + 1) {
-                    tile.sprite.set_texture(tex_man.get(TextureId::Corner), true); // bright
+                    tile.set_texture(tex_man.get(TextureId::Corner)); // bright
                     tile.sprite.set_rotation(180.);
                     tile.bounds[0] = Some(FloatRect::new(0., 0., 6., 6.));
                 } else if j == 0 {
-                    tile.sprite.set_texture(tex_man.get(TextureId::Wall), true);
+                    tile.set_texture(tex_man.get(TextureId::Wall));
                     tile.sprite.set_rotation(270.);
                     tile.bounds[0] = Some(FloatRect::new(58., 0., 6., 64.));
                 } else if j == size_x + 1 {
-                    tile.sprite.set_texture(tex_man.get(TextureId::Wall), true);
+                    tile.set_texture(tex_man.get(TextureId::Wall));
                     tile.sprite.set_rotation(90.);
 
                     tile.bounds[0] = Some(FloatRect::new(0., 0., 6., 64.));
                 } else if i == 0 && j == size_x / 2 {
                     tile.tile_type = TileType::Door(Direction::North);
-                    tile.sprite.set_texture(tex_man.get(TextureId::DoorClosed), true);
+                    tile.set_texture(tex_man.get(TextureId::DoorClosed));
                 } else if i == 0 {
-                    tile.sprite.set_texture(tex_man.get(TextureId::Wall), true);
+                    tile.set_texture(tex_man.get(TextureId::Wall));
                     tile.bounds[0] = Some(FloatRect::new(0., 58., 64., 6.));
                 } else if i == size_y + 1 && j == size_x / 2 {
                     tile.tile_type = TileType::Door(Direction::South);
-                    tile.sprite.set_texture(tex_man.get(TextureId::DoorClosed), true);
+                    tile.set_texture(tex_man.get(TextureId::DoorClosed));
                     tile.sprite.set_rotation(180.);
                 } else if i == size_y + 1 {
-                    tile.sprite.set_texture(tex_man.get(TextureId::Wall), true);
+                    tile.set_texture(tex_man.get(TextureId::Wall));
                     tile.sprite.set_rotation(180.);
                     tile.bounds[0] = Some(FloatRect::new(0., 0., 64., 6.));
                 } else {
-                    tile.sprite.set_texture(tex_man.get(TextureId::Floor), true);
+                    tile.set_texture(tex_man.get(TextureId::Floor));
                     tile.is_solid = false;
                 }
                 tiles[i as usize].push(tile);
@@ -141,6 +199,8 @@ impl<'a> Wagon<'a> {
             connected_to: [None, None],
             rotation: 0.,
             center: center,
+            batches: RefCell::new(vec![]),
+            dirty: Cell::new(true),
         }
     }
 
@@ -218,6 +278,120 @@ impl<'a> Wagon<'a> {
         self.center
     }
 
+    /// `pos` in the wagon's own grid, measured in tiles from the center of tile `(0, 0)` with the
+    /// wagon's rotation undone. Tile `(i, j)` has its center at `(j, i)`.
+    fn to_grid(&self, pos: Vector2f) -> Vector2f {
+        let relative = pos - self.tiles[0][0].sprite.get_position();
+        let local = formula_rot(&relative, -self.rotation.to_radians());
+        Vector2f::new(local.x / TILE_SIZE_X as f32, local.y / TILE_SIZE_Y as f32)
+    }
+
+    /// `(row, column)` of the tile containing `pos`, solid or not. Works straight from the grid
+    /// so it costs the same however big the wagon is.
+    pub fn tile_index_at(&self, pos: Vector2f) -> Option<(usize, usize)> {
+        let g = self.to_grid(pos);
+        let (i, j) = ((g.y + 0.5).floor(), (g.x + 0.5).floor());
+        if i < 0. || j < 0. || i as usize >= self.tiles.len() || j as usize >= self.tiles[0].len() {
+            return None;
+        }
+        Some((i as usize, j as usize))
+    }
+
+    /// Rows and columns to look through for every tile whose center lies in `rect`. When the wagon
+    /// is turned this covers a few tiles more than needed, never fewer.
+    pub fn tiles_centered_in(&self, rect: &FloatRect) -> (Range<usize>, Range<usize>) {
+        let corners = [Vector2f::new(rect.left, rect.top),
+                       Vector2f::new(rect.left + rect.width, rect.top),
+                       Vector2f::new(rect.left, rect.top + rect.height),
+                       Vector2f::new(rect.left + rect.width, rect.top + rect.height)];
+        let grid: Vec<Vector2f> = corners.iter().map(|&c| self.to_grid(c)).collect();
+        let min_x = grid.iter().map(|g| g.x).fold(::std::f32::MAX, f32::min);
+        let max_x = grid.iter().map(|g| g.x).fold(::std::f32::MIN, f32::max);
+        let min_y = grid.iter().map(|g| g.y).fold(::std::f32::MAX, f32::min);
+        let max_y = grid.iter().map(|g| g.y).fold(::std::f32::MIN, f32::max);
+
+        let span = |min: f32, max: f32, len: usize| -> Range<usize> {
+            let from = min.ceil().max(0.);
+            let to = (max.floor() + 1.).min(len as f32);
+            if to <= from {
+                0..0
+            } else {
+                from as usize..to as usize
+            }
+        };
+        (span(min_y, max_y, self.tiles.len()), span(min_x, max_x, self.tiles[0].len()))
+    }
+
+    /// Has the tile batches built again before the next draw. Call after changing a tile's
+    /// sprite from outside the wagon.
+    pub fn mark_dirty(&self) {
+        self.dirty.set(true);
+    }
+
+    /// Builds one quad per textured tile, with tile `(i, j)` centered on `(j, i)` tiles from the
+    /// origin and turned by its rotation relative to the wagon's.
+    fn rebuild_batches(&self) {
+        let mut batches: Vec<(&'a Texture, VertexArray)> = vec![];
+        for (i, row) in self.tiles.iter().enumerate() {
+            for (j, tile) in row.iter().enumerate() {
+                let texture = match tile.texture {
+                    Some(texture) => texture,
+                    None => continue,
+                };
+                let idx = match batches.iter().position(|&(t, _)| t as *const Texture == texture as *const Texture) {
+                    Some(idx) => idx,
+                    None => {
+                        let mut va = VertexArray::new().unwrap();
+                        va.set_primitive_type(PrimitiveType::sfQuads);
+                        batches.push((texture, va));
+                        batches.len() - 1
+                    }
+                };
+
+                let size = texture.get_size();
+                let (w, h) = (size.x as f32, size.y as f32);
+                let center = Vector2f::new((j as u32 * TILE_SIZE_X) as f32, (i as u32 * TILE_SIZE_Y) as f32);
+                let angle = (tile.sprite.get_rotation() - self.rotation).to_radians();
+                let origin = tile.sprite.get_origin();
+                let color = tile.sprite.get_color();
+                for &(u, v) in [(0., 0.), (w, 0.), (w, h), (0., h)].iter() {
+                    let corner = formula_rot(&(Vector2f::new(u, v) - origin), angle);
+                    batches[idx].1.append(&Vertex::new(&(center + corner), &color, &Vector2f::new(u, v)));
+                }
+            }
+        }
+
+        *self.batches.borrow_mut() = batches;
+        self.dirty.set(false);
+    }
+
+    /// Turns the floor tile at `(i, j)` into a piece of furniture.
+    pub fn place_furniture(&mut self, i: usize, j: usize, tile_type: TileType) {
+        let tile = &mut self.tiles[i][j];
+        if tile.is_solid {
+            panic!("furniture can only be placed on floor tiles");
+        }
+
+        // no furniture textures yet, so tint the floor instead
+        match tile_type {
+            TileType::Bed => tile.sprite.set_color(&Color::new_rgb(120, 150, 255)),
+            TileType::Galley => tile.sprite.set_color(&Color::new_rgb(255, 170, 90)),
+            TileType::Storage => tile.sprite.set_color(&Color::new_rgb(170, 130, 90)),
+            TileType::Firebox => tile.sprite.set_color(&Color::new_rgb(220, 70, 40)),
+            _ => {}
+        }
+
+        tile.tile_type = tile_type;
+        self.dirty.set(true);
+    }
+
+    /// Turns the floor tile at `(i, j)` into a storage tile holding up to `capacity` items.
+    pub fn place_storage(&mut self, i: usize, j: usize, capacity: u32) -> &mut Storage {
+        self.place_furniture(i, j, TileType::Storage);
+        self.tiles[i][j].storage = Some(Storage::new(capacity));
+        self.tiles[i][j].storage.as_mut().unwrap()
+    }
+
     /// Connects wagon `other` to the *left* side of wagon `self`.
     pub fn connect(&mut self, other: &mut Wagon<'a>, tex_man: &'a TextureManager) {
         let self_height = self.tiles.len();
@@ -227,19 +401,17 @@ impl<'a> Wagon<'a> {
         let other_height_half = other_height / 2;
 
         self.tiles[self_height_half - 1][0]
-            .sprite
-            .set_texture(tex_man.get(TextureId::ConnectorTop), true);
+            .set_texture(tex_man.get(TextureId::ConnectorTop));
         self.tiles[self_height_half - 1][0]
             .sprite
             .set_rotation(0.);
         self.tiles[self_height_half - 1][0].bounds[1] = Some(FloatRect::new(0., 58., 64., 6.));
 
-        self.tiles[self_height_half][0].sprite.set_texture(tex_man.get(TextureId::Floor), true);
+        self.tiles[self_height_half][0].set_texture(tex_man.get(TextureId::Floor));
         self.tiles[self_height_half][0].is_solid = false;
 
         self.tiles[self_height_half + 1][0]
-            .sprite
-            .set_texture(tex_man.get(TextureId::ConnectorBottom), true);
+            .set_texture(tex_man.get(TextureId::ConnectorBottom));
         self.tiles[self_height_half + 1][0]
             .sprite
             .set_rotation(0.);
@@ -247,8 +419,7 @@ impl<'a> Wagon<'a> {
         self.tiles[self_height_half + 1][0].bounds[1] = Some(FloatRect::new(0., 0., 64., 6.));
 
         other.tiles[other_height_half - 1][other_width - 1]
-            .sprite
-            .set_texture(tex_man.get(TextureId::WallConnectedTop), true);
+            .set_texture(tex_man.get(TextureId::WallConnectedTop));
         other.tiles[other_height_half - 1][other_width - 1]
             .sprite
             .set_rotation(0.);
@@ -258,8 +429,7 @@ impl<'a> Wagon<'a> {
             tile
         };
         other.tiles[other_height_half + 1][other_width - 1]
-            .sprite
-            .set_texture(tex_man.get(TextureId::WallConnectedBottom), true);
+            .set_texture(tex_man.get(TextureId::WallConnectedBottom));
         other.tiles[other_height_half + 1][other_width - 1]
             .sprite
             .set_rotation(0.);
@@ -271,6 +441,9 @@ impl<'a> Wagon<'a> {
             -((other_height_half - self_height_half) as i32)
         } * TILE_SIZE_Y as i32;
 
+        self.mark_dirty();
+        other.mark_dirty();
+
         let self_center = self.get_origin();
 
         other.set_position2f(( self_center.x - ((other_width - 1) * TILE_SIZE_X as usize) as f32) as f32,
@@ -280,27 +453,147 @@ impl<'a> Wagon<'a> {
 
 impl<'a> Drawable for Wagon<'a> {
     fn draw<RT: RenderTarget>(&self, render_target: &mut RT, render_states: &mut RenderStates) {
-        for i in 0..(self.tiles.len()) {
-            for j in 0..(self.tiles[i].len()) {
-                render_target.draw(&self.tiles[i][j].sprite);
-                // if self.tiles[i][j].is_solid {
-                //     let bounds = self.tiles[i][j].bounds;
-                //     for b in bounds.iter() {
-                //         let b = if *b != None {
-                //             b.unwrap()
-                //         }
-                //         else {
-                //             continue;
-                //         };
-                //         let mut shape = RectangleShape::new().unwrap();
-                //         shape.set_fill_color(&Color::new_rgba(0, 0, 255, 100));
-                //         shape.set_size2f(b.width as f32, b.height as f32);
-                //         shape.set_position2f(self.tiles[i][j].sprite.get_position().x + b.left as f32,
-                //                              self.tiles[i][j].sprite.get_position().y + b.top as f32);
-
-                //         render_target.draw(&shape);
-                //     }
-                // }
+        if self.dirty.get() {
+            self.rebuild_batches();
+        }
+
+        // the batches are in grid space, place them where tile (0, 0) is and turn them with the wagon
+        let origin = self.tiles[0][0].sprite.get_position();
+        let mut states = RenderStates::default();
+        states.transform = render_states.transform;
+        states.transform.translate(origin.x, origin.y);
+        states.transform.rotate(self.rotation);
+
+        for &(texture, ref va) in self.batches.borrow().iter() {
+            states.texture = Some(texture);
+            render_target.draw_with_renderstates(va, &mut states);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Laid out like `Wagon::new`, walls turned the same way, but without textures to load.
+    fn bare_wagon(size_x: u32, size_y: u32) -> Wagon<'static> {
+        let mut tiles = vec![];
+        for i in 0..(size_y + 2) {
+            let mut row = vec![];
+            for j in 0..(size_x + 2) {
+                let mut tile = Tile::new();
+                tile.sprite.set_texture_rect(&IntRect::new(0, 0, TILE_SIZE_X as i32, TILE_SIZE_Y as i32));
+                tile.sprite.set_origin2f(TILE_SIZE_X as f32 / 2., TILE_SIZE_Y as f32 / 2.);
+                tile.sprite.set_position2f((j * TILE_SIZE_X) as f32, (i * TILE_SIZE_Y) as f32);
+                if j == 0 {
+                    tile.sprite.set_rotation(270.);
+                } else if j == size_x + 1 {
+                    tile.sprite.set_rotation(90.);
+                } else if i == size_y + 1 {
+                    tile.sprite.set_rotation(180.);
+                }
+                tile.is_solid = i == 0 || j == 0 || i == size_y + 1 || j == size_x + 1;
+                row.push(tile);
+            }
+            tiles.push(row);
+        }
+
+        let center = Vector2f::new((size_x + 1) as f32 * TILE_SIZE_X as f32 / 2.,
+                                   (size_y + 1) as f32 * TILE_SIZE_Y as f32 / 2.);
+        Wagon {
+            tiles: tiles,
+            connected_to: [None, None],
+            rotation: 0.,
+            center: center,
+            batches: RefCell::new(vec![]),
+            dirty: Cell::new(true),
+        }
+    }
+
+    fn placed(angle: f32) -> Wagon<'static> {
+        let mut wagon = bare_wagon(6, 3);
+        wagon.set_position2f(500., 300.);
+        wagon.set_rotation(angle);
+        wagon
+    }
+
+    const ANGLES: [f32; 3] = [0., 30., 90.];
+
+    /// How tiles were found before the grid math, one by one.
+    fn scan(wagon: &Wagon, pos: Vector2f) -> Option<(usize, usize)> {
+        for (i, row) in wagon.tiles.iter().enumerate() {
+            for (j, t) in row.iter().enumerate() {
+                if t.contains(pos) {
+                    return Some((i, j));
+                }
+            }
+        }
+        None
+    }
+
+    /// Right on the line between two tiles rounding picks either, there's no right answer.
+    fn near_edge(g: f32) -> bool {
+        (g + 0.5 - (g + 0.5).round()).abs() < 0.01
+    }
+
+    #[test]
+    fn tile_index_at_matches_scan() {
+        for &angle in ANGLES.iter() {
+            let wagon = placed(angle);
+            let center = wagon.get_origin();
+            let mut checked = 0;
+            // reaches well past the wagon's ends on every side
+            for x in -40..41 {
+                for y in -40..41 {
+                    let pos = center + Vector2f::new(x as f32 * 9.7, y as f32 * 9.7);
+                    let g = wagon.to_grid(pos);
+                    if near_edge(g.x) || near_edge(g.y) {
+                        continue;
+                    }
+                    assert_eq!(wagon.tile_index_at(pos),
+                               scan(&wagon, pos),
+                               "at {} {} turned {}",
+                               pos.x,
+                               pos.y,
+                               angle);
+                    checked += 1;
+                }
+            }
+            assert!(checked > 5000);
+        }
+    }
+
+    #[test]
+    fn tiles_centered_in_covers_scan() {
+        for &angle in ANGLES.iter() {
+            let wagon = placed(angle);
+            let center = wagon.get_origin();
+            for &(width, height) in [(10., 10.), (100., 80.), (400., 250.), (900., 600.)].iter() {
+                for k in 0..25 {
+                    let rect = FloatRect::new(center.x - 350. + k as f32 * 31.3,
+                                              center.y - 240. + k as f32 * 17.9,
+                                              width,
+                                              height);
+                    let (rows, columns) = wagon.tiles_centered_in(&rect);
+
+                    let mut expected = 0;
+                    for (i, row) in wagon.tiles.iter().enumerate() {
+                        for (j, t) in row.iter().enumerate() {
+                            if rect.contains(t.sprite.get_position()) {
+                                assert!(rows.start <= i && i < rows.end && columns.start <= j && j < columns.end,
+                                        "tile {},{} missed turned {}",
+                                        i,
+                                        j,
+                                        angle);
+                                expected += 1;
+                            }
+                        }
+                    }
+                    // square to the world the ranges are exact
+                    if angle == 0. {
+                        assert_eq!(rows.len() * columns.len(), expected);
+                    }
+                }
             }
         }
     }
@@ -0,0 +1,191 @@
+use rand::Rng;
+use sfml::graphics::FloatRect;
+use sfml::system::Vector2f;
+
+use enemy::EnemyKind;
+use world::World;
+use rng::GameRng;
+use game_consts::*;
+
+/// How far from the ends of the train waves show up.
+const SPAWN_DISTANCE: f32 = 12. * TILE_SIZE_X as f32;
+/// Nothing spawns closer than this to the edge of the camera view.
+const SPAWN_MARGIN: f32 = 3. * TILE_SIZE_X as f32;
+/// Gap between enemies of the same wave.
+const SPAWN_SPACING: f32 = 48.;
+/// How far from the track, in tiles, enemies show up.
+const TRACK_OFFSET: (f32, f32) = (3., 6.);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WaveClock {
+    /// Waves come every so many seconds.
+    Time,
+    /// Waves come every so much distance travelled.
+    Distance,
+}
+
+/// How often waves come and how big and nasty they get as the journey goes on.
+#[derive(Clone, Debug)]
+pub struct DifficultyCurve {
+    pub clock: WaveClock,
+    /// Seconds or distance between two waves, depending on `clock`.
+    pub interval: f32,
+    /// `(progress, difficulty)` in the same unit as `interval`. Difficulty is interpolated
+    /// between them and stays flat past the first and last one.
+    pub points: Vec<(f32, f32)>,
+    /// Enemies in a wave at difficulty 0.
+    pub base_count: f32,
+    /// Extra enemies per point of difficulty.
+    pub count_per_level: f32,
+    /// `(kind, difficulty it starts showing up at, weight)`. The weight grows with
+    /// every point of difficulty past the start, so late kinds take over slowly.
+    pub kinds: Vec<(EnemyKind, f32, f32)>,
+}
+
+impl DifficultyCurve {
+    pub fn new(clock: WaveClock, interval: f32) -> Self {
+        DifficultyCurve {
+            clock: clock,
+            interval: interval,
+            points: vec![],
+            base_count: 2.,
+            count_per_level: 1.,
+            kinds: vec![],
+        }
+    }
+
+    pub fn difficulty_at(&self, progress: f32) -> f32 {
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return 0.,
+        };
+        if progress <= first.0 {
+            return first.1;
+        }
+        for pair in self.points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            if progress <= x1 {
+                if x1 <= x0 {
+                    return y1;
+                }
+                return y0 + (y1 - y0) * (progress - x0) / (x1 - x0);
+            }
+        }
+        last.1
+    }
+
+    pub fn count_at(&self, difficulty: f32) -> usize {
+        (self.base_count + self.count_per_level * difficulty).round().max(0.) as usize
+    }
+
+    fn weight_of(&self, &(_, from, weight): &(EnemyKind, f32, f32), difficulty: f32) -> f32 {
+        if difficulty < from {
+            0.
+        } else {
+            weight * (1. + difficulty - from)
+        }
+    }
+
+    /// A random kind for a wave at `difficulty`, bandits if nothing else fits.
+    fn pick_kind<R: Rng>(&self, difficulty: f32, rng: &mut R) -> EnemyKind {
+        let total: f32 = self.kinds.iter().map(|k| self.weight_of(k, difficulty)).sum();
+        if total <= 0. {
+            return EnemyKind::Bandit;
+        }
+
+        let mut pick = rng.next_f32() * total;
+        for k in self.kinds.iter() {
+            let weight = self.weight_of(k, difficulty);
+            if pick < weight {
+                return k.0;
+            }
+            pick -= weight;
+        }
+        self.kinds.last().unwrap().0
+    }
+}
+
+/// Sends waves of enemies at the train following a `DifficultyCurve`.
+pub struct WaveSpawner {
+    curve: Option<DifficultyCurve>,
+    /// Progress at which the next wave comes.
+    next_at: f32,
+    /// Waves sent so far.
+    pub waves: u32,
+}
+
+impl WaveSpawner {
+    /// Without a curve no waves come at all.
+    pub fn new(curve: Option<DifficultyCurve>) -> Self {
+        let next_at = curve.as_ref().map(|c| c.interval).unwrap_or(0.);
+        WaveSpawner {
+            curve: curve,
+            next_at: next_at,
+            waves: 0,
+        }
+    }
+
+    /// The kinds of the enemies in the next wave, empty until it's due.
+    pub fn update(&mut self, time: f32, distance: f32, rng: &mut GameRng) -> Vec<EnemyKind> {
+        let curve = match self.curve {
+            Some(ref curve) if curve.interval > 0. => curve,
+            _ => return vec![],
+        };
+        let progress = match curve.clock {
+            WaveClock::Time => time,
+            WaveClock::Distance => distance.abs(),
+        };
+        if progress < self.next_at {
+            return vec![];
+        }
+        self.next_at = progress + curve.interval;
+        self.waves += 1;
+
+        let difficulty = curve.difficulty_at(progress);
+        let kinds: Vec<EnemyKind> = (0..curve.count_at(difficulty))
+            .map(|_| curve.pick_kind(difficulty, rng))
+            .collect();
+        kinds
+    }
+}
+
+/// Where `n` enemies should show up: beside the track a little way off the train, which spans
+/// `front_x` to `rear_x`. They come from the side the world scrolls in from so the train runs
+/// into them, either side if it stands still. Spots the player would see pop up are moved out
+/// past the edge of `view`.
+pub fn spawn_points(n: usize,
+                    view: FloatRect,
+                    world: &World,
+                    (front_x, rear_x): (f32, f32),
+                    train_pos: Vector2f,
+                    speed: f32,
+                    rng: &mut GameRng)
+                    -> Vec<Vector2f> {
+    let ahead = if speed == 0. { rng.gen() } else { speed > 0. };
+    let seen = FloatRect::new(view.left - SPAWN_MARGIN,
+                              view.top - SPAWN_MARGIN,
+                              view.width + 2. * SPAWN_MARGIN,
+                              view.height + 2. * SPAWN_MARGIN);
+
+    (0..n)
+        .map(|k| {
+            let along = SPAWN_DISTANCE + (k / 2) as f32 * SPAWN_SPACING;
+            let x = if ahead { front_x + along } else { rear_x - along };
+            let side = if k % 2 == 0 { -1. } else { 1. };
+            let tiles = TRACK_OFFSET.0 + rng.next_f32() * (TRACK_OFFSET.1 - TRACK_OFFSET.0);
+            let y = world.track_y_at(x).unwrap_or(train_pos.y) + side * tiles * TILE_SIZE_Y as f32;
+            if !seen.contains(Vector2f::new(x, y)) {
+                return Vector2f::new(x, y);
+            }
+
+            // on screen, so further along the track in the same direction until it isn't
+            let x = if ahead {
+                seen.left + seen.width + (k / 2) as f32 * SPAWN_SPACING
+            } else {
+                seen.left - (k / 2) as f32 * SPAWN_SPACING
+            };
+            let y = world.track_y_at(x).unwrap_or(train_pos.y) + side * tiles * TILE_SIZE_Y as f32;
+            Vector2f::new(x, y)
+        })
+        .collect()
+}
@@ -10,6 +10,8 @@ pub struct World<'a> {
     pub rails: Vec<RectangleShape<'a>>,
     pub connectors: Vec<RectangleShape<'a>>,
     pub curves: Vec<(QuadraticBezierSegment, QuadraticBezierSegment)>,
+    /// How far the ground moved in the last simulation step.
+    pub last_scroll: f32,
 }
 
 fn new_curve(from: Vec2, ctrl: Vec2, to: Vec2) -> QuadraticBezierSegment {
@@ -41,6 +43,16 @@ fn populate_vec_with_shapes_from_curve(curve: &mut QuadraticBezierSegment,
 }
 
 impl<'a> World<'a> {
+    pub fn new() -> Self {
+        World {
+            bgs: vec![],
+            rails: vec![],
+            connectors: vec![],
+            curves: vec![],
+            last_scroll: 0.,
+        }
+    }
+
     pub fn init(&mut self, tm: &'a TextureManager) {
         self.curves.push(
             (new_curve(Vec2::new(0., 300.),
@@ -99,9 +111,14 @@ impl<'a> World<'a> {
     }
 
     pub fn update(&mut self, speed: f32) {
+        self.last_scroll = speed;
+
+        // positive speed means the train is backing up, so wrap the other way around too
         for bg in self.bgs.iter_mut() {
             if bg.get_position().x <= -(WINDOW_SIZE_X as f32) {
                 bg.move2f((WINDOW_SIZE_X * 2) as f32, 0.);
+            } else if speed > 0. && bg.get_position().x >= (WINDOW_SIZE_X * 2) as f32 {
+                bg.move2f(-((WINDOW_SIZE_X * 2) as f32), 0.);
             }
         }
 
@@ -112,6 +129,8 @@ impl<'a> World<'a> {
         for rail in self.rails.iter_mut() {
             if rail.get_position().x <= 0. {
                 rail.move2f(self.curves[0].0.to.x, 0.);
+            } else if speed > 0. && rail.get_position().x >= self.curves[0].0.to.x {
+                rail.move2f(-self.curves[0].0.to.x, 0.);
             }
             rail.move2f(speed, 0.);
         }
@@ -119,11 +138,23 @@ impl<'a> World<'a> {
         for ctr in self.connectors.iter_mut() {
             if ctr.get_position().x <= -(WINDOW_SIZE_X as f32) {
                 ctr.move2f((WINDOW_SIZE_X * 2) as f32, 0.);
+            } else if speed > 0. && ctr.get_position().x >= (WINDOW_SIZE_X * 2) as f32 {
+                ctr.move2f(-((WINDOW_SIZE_X * 2) as f32), 0.);
             }
             ctr.move2f(speed, 0.);
         }
     }
 
+    /// Height of the rail under screen x `x`, if there is one.
+    pub fn track_y_at(&self, x: f32) -> Option<f32> {
+        for rail in self.rails.iter() {
+            if x > rail.get_position().x && x < rail.get_size().x + rail.get_position().x {
+                return Some(rail.get_position().y);
+            }
+        }
+        None
+    }
+
     // pub fn recalculate_drawables(&mut self, view: &View, origin_pixels: &Vector2f, tm: &'a TextureManager) {
     //     println!("{:?}", origin_pixels);
     //     self.bgs.clear();